@@ -1,327 +1,1810 @@
-use pyo3::prelude::*;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
-use std::str;
-
-use crate::objects::services::ffprobe::{ServicesFFProbeRequest, ServicesFFProbeResponse};
-use crate::objects::services::keep_alive::KeepAliveMessage;
-use crate::objects::services::ping::PingRequestResponse;
-use crate::objects::services::storage::notify_message::NotifyMessage;
-use crate::objects::services::storage::stream_track_unit_elements::{
-    StreamTrackUnitElementsRequest, StreamTrackUnitElementsResponse,
-};
-use crate::objects::services::storage::stream_track_units::{
-    StreamTrackUnitsRequest, StreamTrackUnitsResponse,
-};
-use crate::objects::services::storage::stream_tracks::{StreamTracksRequest, StreamTracksResponse};
-use crate::objects::services::storage::unit_element_message::UnitElementMessage;
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+
+#[cfg(feature = "python")]
+use crate::codec::Codec;
+use crate::compression::{Compression, CompressionPolicy};
+use crate::discovery::{self, SchemaScan};
+use crate::encryption::{self, EncryptionKeys, Sealed};
+use crate::envelope::{self, EnvelopeFormat, Framing};
+use crate::envelope::{MESSAGE_BATCH_SCHEMA, MESSAGE_BATCH_SCHEMA_JSON};
+use crate::error::{ProtocolError, Result};
+use crate::fingerprint::schema_fingerprint;
+use crate::headers::{Headers, HEADERS_SCHEMA_JSON};
+use crate::introspection::SchemaInfo;
+use crate::json::{json_to_value, value_to_json};
+use crate::limits::DecodeLimits;
+#[cfg(feature = "python")]
+use crate::objects::{AnyMessage, ToProtocolMessage};
+#[cfg(feature = "python")]
+use crate::python;
+use crate::record::RecordFields;
+#[cfg(feature = "python")]
+use crate::registry::MessageHandler;
+use crate::schemas::EMBEDDED_SCHEMAS;
+use crate::signing::{self, KeyRing, Signature, SigningKey, VerifyPolicy};
 use avro_rs::schema::Name;
 use avro_rs::types::{Record, Value};
 use avro_rs::{from_avro_datum, to_avro_datum, Schema};
-use log::warn;
-use pyo3::PyClass;
+#[cfg(feature = "python")]
+use pyo3::exceptions::PyTypeError;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
 
 use crate::utils;
 
 type SchemaDirectory = HashMap<String, Schema>;
 
 pub const STORAGE_SCHEMAS: &str = "storage";
-pub const TRACK_TYPE_SCHEMA: &str = "insight.storage.TrackType.avsc";
-pub const TRACK_INFO_SCHEMA: &str = "insight.storage.TrackInfo.avsc";
-pub const UNIT_SCHEMA: &str = "insight.storage.Unit.avsc";
-pub const UNIT_ELEMENT_MESSAGE_SCHEMA: &str = "insight.storage.UnitElementMessage.avsc";
-pub const UNIT_ELEMENT_VALUE_SCHEMA: &str = "insight.storage.UnitElementValue.avsc";
+pub const TRACK_TYPE_SCHEMA: &str = "insight.storage.TrackType";
+pub const TRACK_INFO_SCHEMA: &str = "insight.storage.TrackInfo";
+pub const UNIT_SCHEMA: &str = "insight.storage.Unit";
+pub const UNIT_ELEMENT_MESSAGE_SCHEMA: &str = "insight.storage.UnitElementMessage";
+pub const UNIT_ELEMENT_VALUE_SCHEMA: &str = "insight.storage.UnitElementValue";
 
 pub const TRANSPORT_SCHEMAS: &str = "transport";
-pub const NOTIFY_MESSAGE_SCHEMA: &str = "insight.transport.NotifyMessage.avsc";
-pub const STREAM_TRACKS_REQUEST_SCHEMA: &str = "insight.transport.StreamTracksRequest.avsc";
-pub const STREAM_TRACKS_RESPONSE_SCHEMA: &str = "insight.transport.StreamTracksResponse.avsc";
+pub const NOTIFY_MESSAGE_SCHEMA: &str = "insight.transport.NotifyMessage";
+pub const STREAM_TRACKS_REQUEST_SCHEMA: &str = "insight.transport.StreamTracksRequest";
+pub const STREAM_TRACKS_RESPONSE_SCHEMA: &str = "insight.transport.StreamTracksResponse";
 pub const STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA: &str =
-    "insight.transport.StreamTrackUnitElementsRequest.avsc";
+    "insight.transport.StreamTrackUnitElementsRequest";
 pub const STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA: &str =
+    "insight.transport.StreamTrackUnitElementsResponse";
+pub const STREAM_TRACK_UNITS_REQUEST_SCHEMA: &str = "insight.transport.StreamTrackUnitsRequest";
+pub const STREAM_TRACK_UNITS_RESPONSE_SCHEMA: &str = "insight.transport.StreamTrackUnitsResponse";
+pub const MESSAGE_ENVELOPE_SCHEMA: &str = "insight.transport.MessageEnvelope";
+pub const PING_REQUEST_RESPONSE_SCHEMA: &str = "insight.transport.PingRequestResponse";
+pub const KEEPALIVE_MESSAGE_SCHEMA: &str = "insight.transport.KeepAliveMessage";
+
+pub const SERVICE_FFPROBE_SCHEMAS: &str = "services/ffprobe";
+pub const SERVICES_FFPROBE_REQUEST_SCHEMA: &str = "insight.ffprobe.Request";
+pub const SERVICES_FFPROBE_RESPONSE_SCHEMA: &str = "insight.ffprobe.Response";
+
+// The `.avsc` file names the constants above held up to 0.2.1, which the catalog still resolves.
+#[deprecated(note = "use `TRACK_TYPE_SCHEMA`, which holds the full name")]
+pub const TRACK_TYPE_SCHEMA_FILE: &str = "insight.storage.TrackType.avsc";
+#[deprecated(note = "use `TRACK_INFO_SCHEMA`, which holds the full name")]
+pub const TRACK_INFO_SCHEMA_FILE: &str = "insight.storage.TrackInfo.avsc";
+#[deprecated(note = "use `UNIT_SCHEMA`, which holds the full name")]
+pub const UNIT_SCHEMA_FILE: &str = "insight.storage.Unit.avsc";
+#[deprecated(note = "use `UNIT_ELEMENT_MESSAGE_SCHEMA`, which holds the full name")]
+pub const UNIT_ELEMENT_MESSAGE_SCHEMA_FILE: &str = "insight.storage.UnitElementMessage.avsc";
+#[deprecated(note = "use `UNIT_ELEMENT_VALUE_SCHEMA`, which holds the full name")]
+pub const UNIT_ELEMENT_VALUE_SCHEMA_FILE: &str = "insight.storage.UnitElementValue.avsc";
+#[deprecated(note = "use `NOTIFY_MESSAGE_SCHEMA`, which holds the full name")]
+pub const NOTIFY_MESSAGE_SCHEMA_FILE: &str = "insight.transport.NotifyMessage.avsc";
+#[deprecated(note = "use `STREAM_TRACKS_REQUEST_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACKS_REQUEST_SCHEMA_FILE: &str = "insight.transport.StreamTracksRequest.avsc";
+#[deprecated(note = "use `STREAM_TRACKS_RESPONSE_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACKS_RESPONSE_SCHEMA_FILE: &str = "insight.transport.StreamTracksResponse.avsc";
+#[deprecated(note = "use `STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA_FILE: &str =
+    "insight.transport.StreamTrackUnitElementsRequest.avsc";
+#[deprecated(note = "use `STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA_FILE: &str =
     "insight.transport.StreamTrackUnitElementsResponse.avsc";
-pub const STREAM_TRACK_UNITS_REQUEST_SCHEMA: &str =
+#[deprecated(note = "use `STREAM_TRACK_UNITS_REQUEST_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACK_UNITS_REQUEST_SCHEMA_FILE: &str =
     "insight.transport.StreamTrackUnitsRequest.avsc";
-pub const STREAM_TRACK_UNITS_RESPONSE_SCHEMA: &str =
+#[deprecated(note = "use `STREAM_TRACK_UNITS_RESPONSE_SCHEMA`, which holds the full name")]
+pub const STREAM_TRACK_UNITS_RESPONSE_SCHEMA_FILE: &str =
     "insight.transport.StreamTrackUnitsResponse.avsc";
-pub const MESSAGE_ENVELOPE_SCHEMA: &str = "insight.transport.MessageEnvelope.avsc";
-pub const PING_REQUEST_RESPONSE_SCHEMA: &str = "insight.transport.PingRequestResponse.avsc";
-pub const KEEPALIVE_MESSAGE_SCHEMA: &str = "insight.transport.KeepAliveMessage.avsc";
-
-pub const SERVICE_FFPROBE_SCHEMAS: &str = "services/ffprobe";
-pub const SERVICES_FFPROBE_REQUEST_SCHEMA: &str = "insight.ffprobe.Request.avsc";
-pub const SERVICES_FFPROBE_RESPONSE_SCHEMA: &str = "insight.ffprobe.Response.avsc";
+#[deprecated(note = "use `MESSAGE_ENVELOPE_SCHEMA`, which holds the full name")]
+pub const MESSAGE_ENVELOPE_SCHEMA_FILE: &str = "insight.transport.MessageEnvelope.avsc";
+#[deprecated(note = "use `PING_REQUEST_RESPONSE_SCHEMA`, which holds the full name")]
+pub const PING_REQUEST_RESPONSE_SCHEMA_FILE: &str = "insight.transport.PingRequestResponse.avsc";
+#[deprecated(note = "use `KEEPALIVE_MESSAGE_SCHEMA`, which holds the full name")]
+pub const KEEPALIVE_MESSAGE_SCHEMA_FILE: &str = "insight.transport.KeepAliveMessage.avsc";
+#[deprecated(note = "use `SERVICES_FFPROBE_REQUEST_SCHEMA`, which holds the full name")]
+pub const SERVICES_FFPROBE_REQUEST_SCHEMA_FILE: &str = "insight.ffprobe.Request.avsc";
+#[deprecated(note = "use `SERVICES_FFPROBE_RESPONSE_SCHEMA`, which holds the full name")]
+pub const SERVICES_FFPROBE_RESPONSE_SCHEMA_FILE: &str = "insight.ffprobe.Response.avsc";
 
 pub struct BuilderImpl {
+    /// The current versions of the schemas keyed by Avro full name.
     pub directory: SchemaDirectory,
+    /// Full names and aliases of the schemas, mapped to the full names they resolve to.
+    names: HashMap<String, String>,
+    sources: Vec<String>,
+    /// Every known version of the schemas keyed by fingerprint, the current ones included.
+    versions: HashMap<u64, (String, Schema)>,
+    fingerprints: HashMap<String, u64>,
+    envelope_format: EnvelopeFormat,
+    compression: CompressionPolicy,
+    headers_schema: Schema,
+    batch_schema: Schema,
+    limits: DecodeLimits,
+    checksum: bool,
+    signing_key: Option<SigningKey>,
+    key_ring: KeyRing,
+    verify_policy: VerifyPolicy,
+    encryption: EncryptionKeys,
 }
 
 impl BuilderImpl {
-    pub fn schema_files() -> Vec<(&'static str, &'static str)> {
-        vec![
-            (STORAGE_SCHEMAS, TRACK_TYPE_SCHEMA),
-            (STORAGE_SCHEMAS, TRACK_INFO_SCHEMA),
-            (STORAGE_SCHEMAS, UNIT_SCHEMA),
-            (STORAGE_SCHEMAS, UNIT_ELEMENT_VALUE_SCHEMA),
-            (STORAGE_SCHEMAS, UNIT_ELEMENT_MESSAGE_SCHEMA),
-            (TRANSPORT_SCHEMAS, NOTIFY_MESSAGE_SCHEMA),
-            (TRANSPORT_SCHEMAS, STREAM_TRACKS_REQUEST_SCHEMA),
-            (TRANSPORT_SCHEMAS, STREAM_TRACKS_RESPONSE_SCHEMA),
-            (TRANSPORT_SCHEMAS, STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA),
-            (
-                TRANSPORT_SCHEMAS,
-                STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA,
-            ),
-            (TRANSPORT_SCHEMAS, STREAM_TRACK_UNITS_REQUEST_SCHEMA),
-            (TRANSPORT_SCHEMAS, STREAM_TRACK_UNITS_RESPONSE_SCHEMA),
-            (TRANSPORT_SCHEMAS, PING_REQUEST_RESPONSE_SCHEMA),
-            (TRANSPORT_SCHEMAS, KEEPALIVE_MESSAGE_SCHEMA),
-            (TRANSPORT_SCHEMAS, MESSAGE_ENVELOPE_SCHEMA),
-            (SERVICE_FFPROBE_SCHEMAS, SERVICES_FFPROBE_REQUEST_SCHEMA),
-            (SERVICE_FFPROBE_SCHEMAS, SERVICES_FFPROBE_RESPONSE_SCHEMA),
-        ]
-    }
-
-    pub fn new(path_prefix: &str) -> BuilderImpl {
-        let schemas_raw: Vec<String> = Self::schema_files()
+    /// The built-in schemas by the directory of the `API` tree they are stored in.
+    pub(crate) fn schema_files() -> Vec<(&'static str, &'static str)> {
+        EMBEDDED_SCHEMAS
+            .iter()
+            .map(|(dir, name, _)| (*dir, *name))
+            .collect()
+    }
+
+    pub fn new(path_prefix: &str) -> Result<BuilderImpl> {
+        let schemas_raw = Self::schema_files()
             .iter()
             .map(|schema| {
                 utils::load_file(
                     Path::new(path_prefix).join(Path::new(schema.0)).as_path(),
-                    schema.1,
+                    &Self::file_name(schema.1),
                 )
             })
+            .collect::<Result<Vec<String>>>()?;
+        Self::from_sources(schemas_raw)
+    }
+
+    /// Loads the `.avsc` files found anywhere under the root in the order their references
+    /// require. The files which fail to load are reported in the scan and left out of the
+    /// catalog; the built-in schemas the tree lacks are taken from the embedded ones.
+    pub fn discover(root: &str) -> Result<(BuilderImpl, SchemaScan)> {
+        let (sources, scan) = discovery::discover(Path::new(root))?;
+        Ok((Self::from_sources(sources)?, scan))
+    }
+
+    /// Builds the catalog from the schemas bundled into the library.
+    pub fn embedded() -> Result<BuilderImpl> {
+        let schemas_raw = EMBEDDED_SCHEMAS
+            .iter()
+            .map(|(_, _, s)| String::from(*s))
             .collect();
-        let schemas_raw_str: Vec<&str> = schemas_raw.iter().map(|s| s.as_str()).collect();
-
-        let schemas = Schema::parse_list(&schemas_raw_str).unwrap();
-        let mut named_schemas = HashMap::default();
-
-        for s in &schemas {
-            match s {
-                Schema::Enum {
-                    name:
-                        Name {
-                            name,
-                            namespace,
-                            aliases: _,
-                        },
-                    doc: _,
-                    symbols: _,
-                } => {
-                    let mut full_name = namespace
-                        .clone()
-                        .unwrap_or_else(|| String::from("insight.transport"));
-                    full_name.push('.');
-                    full_name.push_str(name);
-                    full_name.push_str(".avsc");
-                    named_schemas.insert(full_name, s.clone());
-                }
-                Schema::Record {
-                    name:
-                        Name {
-                            name,
-                            namespace,
-                            aliases: _,
-                        },
-                    doc: _,
-                    fields: _,
-                    lookup: _,
-                } => {
-                    let mut full_name = namespace
-                        .clone()
-                        .unwrap_or_else(|| String::from("insight.transport"));
-                    full_name.push('.');
-                    full_name.push_str(name);
-                    full_name.push_str(".avsc");
-                    named_schemas.insert(full_name, s.clone());
-                }
-                _ => {
-                    dbg!(s);
-                }
-            };
+        Self::from_sources(schemas_raw)
+    }
+
+    fn from_sources(sources: Vec<String>) -> Result<BuilderImpl> {
+        let schemas = Self::parse_sources(&sources)?;
+        let headers_schema = Schema::parse_str(HEADERS_SCHEMA_JSON)
+            .map_err(|e| ProtocolError::SchemaLoad(e.to_string()))?;
+        let batch_schema = Schema::parse_str(MESSAGE_BATCH_SCHEMA_JSON)
+            .map_err(|e| ProtocolError::SchemaLoad(e.to_string()))?;
+        let mut builder = BuilderImpl {
+            directory: SchemaDirectory::default(),
+            names: HashMap::default(),
+            sources,
+            versions: HashMap::default(),
+            fingerprints: HashMap::default(),
+            envelope_format: EnvelopeFormat::default(),
+            compression: CompressionPolicy::default(),
+            headers_schema,
+            batch_schema,
+            limits: DecodeLimits::default(),
+            checksum: false,
+            signing_key: None,
+            key_ring: KeyRing::default(),
+            verify_policy: VerifyPolicy::default(),
+            encryption: EncryptionKeys::default(),
+        };
+        for s in schemas {
+            if let Some(schema_name) = Self::schema_full_name(&s) {
+                builder.insert_schema(schema_name, s);
+            }
         }
+        Ok(builder)
+    }
+
+    fn parse_sources(sources: &[String]) -> Result<Vec<Schema>> {
+        let schemas_raw_str: Vec<&str> = sources.iter().map(|s| s.as_str()).collect();
+        Schema::parse_list(&schemas_raw_str).map_err(|e| ProtocolError::SchemaLoad(e.to_string()))
+    }
+
+    /// The Avro full name of a named schema, e.g. `insight.storage.Unit`.
+    fn schema_full_name(s: &Schema) -> Option<String> {
+        Self::schema_name(s).map(|name| name.fullname(None))
+    }
+
+    fn schema_name(s: &Schema) -> Option<&Name> {
+        match s {
+            Schema::Record { name, .. }
+            | Schema::Enum { name, .. }
+            | Schema::Fixed { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Aliases of the named schema qualified with its namespace unless they are full names.
+    pub(crate) fn schema_aliases(s: &Schema) -> Vec<String> {
+        let name = match Self::schema_name(s) {
+            Some(name) => name,
+            None => return Vec::new(),
+        };
+        let aliases = name.aliases.iter().flatten();
+        aliases
+            .map(|alias| match &name.namespace {
+                Some(namespace) if !alias.contains('.') => format!("{}.{}", namespace, alias),
+                _ => alias.clone(),
+            })
+            .collect()
+    }
+
+    /// Avro full name the schema source declares, as `Schema::parse_list` keys it.
+    fn source_name(json: &str) -> Option<String> {
+        let value: serde_json::Value = serde_json::from_str(json).ok()?;
+        let name = value.get("name")?.as_str()?;
+        match value.get("namespace").and_then(|n| n.as_str()) {
+            Some(namespace) if !name.contains('.') => Some(format!("{}.{}", namespace, name)),
+            _ => Some(String::from(name)),
+        }
+    }
+
+    /// Parses the schema in place of the known source of the same name, or after all of them.
+    fn parse_source(&self, json: &str) -> Result<(Vec<String>, String, Schema)> {
+        let mut sources = self.sources.clone();
+        let name = Self::source_name(json);
+        let position = match sources
+            .iter()
+            .position(|s| name.is_some() && Self::source_name(s) == name)
+        {
+            Some(position) => {
+                sources[position] = String::from(json);
+                position
+            }
+            None => {
+                sources.push(String::from(json));
+                sources.len() - 1
+            }
+        };
+        let schema = Self::parse_sources(&sources)?.swap_remove(position);
+        let schema_name = Self::schema_full_name(&schema).ok_or_else(|| {
+            ProtocolError::SchemaLoad(String::from(
+                "Only named records, enums and fixed types can be registered",
+            ))
+        })?;
+        Ok((sources, schema_name, schema))
+    }
+
+    fn insert_schema(&mut self, schema_name: String, schema: Schema) {
+        // A full name takes precedence over the alias of another schema.
+        for alias in Self::schema_aliases(&schema) {
+            self.names
+                .entry(alias)
+                .or_insert_with(|| schema_name.clone());
+        }
+        self.names.insert(schema_name.clone(), schema_name.clone());
+        let fingerprint = schema_fingerprint(&schema);
+        self.versions
+            .insert(fingerprint, (schema_name.clone(), schema.clone()));
+        self.fingerprints.insert(schema_name.clone(), fingerprint);
+        self.directory.insert(schema_name, schema);
+    }
+
+    /// Adds the schema to the catalog; it may reference any schema already known.
+    ///
+    /// A schema of a known name becomes its current version, the previous one is still used to
+    /// read the messages written with it. Returns the name the schema is registered under.
+    pub fn register_schema(&mut self, json: &str) -> Result<String> {
+        let (sources, schema_name, schema) = self.parse_source(json)?;
+        self.insert_schema(schema_name.clone(), schema);
+        self.sources = sources;
+        Ok(schema_name)
+    }
+
+    /// Adds another version of a known schema to read the messages written with it; the
+    /// messages are resolved into the current version.
+    ///
+    /// Returns the fingerprint of the version.
+    pub fn register_schema_version(&mut self, json: &str) -> Result<u64> {
+        let (_, schema_name, schema) = self.parse_source(json)?;
+        self.get_known_schema(&schema_name)?;
+        let fingerprint = schema_fingerprint(&schema);
+        self.versions.insert(fingerprint, (schema_name, schema));
+        Ok(fingerprint)
+    }
+
+    /// The name of the `.avsc` file the schema is conventionally stored in, e.g.
+    /// `insight.storage.Unit.avsc`, which the named envelope carries.
+    pub fn file_name(schema_name: &str) -> String {
+        format!("{}.avsc", schema_name)
+    }
+
+    /// The full name the schema is registered under, looked up by full name, by alias or by
+    /// the name of its `.avsc` file the envelopes of the earlier releases carry.
+    pub fn resolve_name(&self, schema_name: &str) -> Option<&str> {
+        self.names
+            .get(schema_name)
+            .or_else(|| {
+                let full_name = schema_name.strip_suffix(".avsc")?;
+                self.names.get(full_name)
+            })
+            .map(String::as_str)
+    }
+
+    /// Same as `resolve_name`, failing on an unknown schema.
+    pub fn known_name(&self, schema_name: &str) -> Result<&str> {
+        self.resolve_name(schema_name)
+            .ok_or_else(|| ProtocolError::UnknownSchema(String::from(schema_name)))
+    }
+
+    /// Fingerprint of the current version of the schema.
+    pub fn fingerprint(&self, schema_name: &str) -> Result<u64> {
+        let schema_name = self.known_name(schema_name)?;
+        self.fingerprints
+            .get(schema_name)
+            .copied()
+            .ok_or_else(|| ProtocolError::UnknownSchema(String::from(schema_name)))
+    }
 
-        BuilderImpl {
-            directory: named_schemas,
+    pub fn get_schema_version(&self, schema_name: &str, fingerprint: u64) -> Result<&Schema> {
+        match self.versions.get(&fingerprint) {
+            Some((name, schema)) if Some(name.as_str()) == self.resolve_name(schema_name) => {
+                Ok(schema)
+            }
+            _ => Err(ProtocolError::UnknownSchemaVersion {
+                schema: String::from(schema_name),
+                fingerprint,
+            }),
         }
     }
 
+    /// The schemas of the catalog by name, in their current versions.
+    pub fn schemas(&self) -> Result<Vec<SchemaInfo>> {
+        let mut names = self.directory.keys().collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| self.schema_info(name))
+            .collect()
+    }
+
+    pub fn schema_info(&self, schema_name: &str) -> Result<SchemaInfo> {
+        let schema_name = self.known_name(schema_name)?;
+        let schema = self.get_known_schema(schema_name)?;
+        Ok(SchemaInfo::new(
+            schema_name,
+            schema,
+            self.fingerprint(schema_name)?,
+        ))
+    }
+
+    /// The Parsing Canonical Form of the current version of the schema, the JSON its
+    /// fingerprint is computed from.
+    pub fn schema_json(&self, schema_name: &str) -> Result<String> {
+        Ok(self.get_known_schema(schema_name)?.canonical_form())
+    }
+
+    pub fn envelope_format(&self) -> EnvelopeFormat {
+        self.envelope_format
+    }
+
+    pub fn set_envelope_format(&mut self, format: EnvelopeFormat) {
+        self.envelope_format = format;
+    }
+
+    pub fn compression(&self) -> CompressionPolicy {
+        self.compression
+    }
+
+    /// Compression of the framed messages; the single-object encoding is never compressed.
+    pub fn set_compression(&mut self, policy: CompressionPolicy) {
+        self.compression = policy;
+    }
+
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// Prefixes the envelopes with their CRC32C; the single-object encoding never carries one.
+    /// Checksummed envelopes are verified whatever the local setting.
+    pub fn set_checksum(&mut self, checksum: bool) {
+        self.checksum = checksum;
+    }
+
+    pub fn decode_limits(&self) -> DecodeLimits {
+        self.limits
+    }
+
+    /// Limits enforced on every received buffer before it is decoded.
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.limits = limits;
+    }
+
+    pub fn signing_key(&self) -> Option<&SigningKey> {
+        self.signing_key.as_ref()
+    }
+
+    /// Signs the packed messages with the key, a batch as a whole; `None` sends them unsigned.
+    /// The single-object encoding is never signed.
+    pub fn set_signing_key(&mut self, key: Option<SigningKey>) {
+        self.signing_key = key;
+    }
+
+    pub fn key_ring(&self) -> &KeyRing {
+        &self.key_ring
+    }
+
+    /// Keys the signatures of the received messages are checked against.
+    pub fn key_ring_mut(&mut self) -> &mut KeyRing {
+        &mut self.key_ring
+    }
+
+    pub fn verify_policy(&self) -> VerifyPolicy {
+        self.verify_policy
+    }
+
+    pub fn set_verify_policy(&mut self, policy: VerifyPolicy) {
+        self.verify_policy = policy;
+    }
+
+    pub fn encryption_keys(&self) -> &EncryptionKeys {
+        &self.encryption
+    }
+
+    /// Keys the payloads are sealed and opened with.
+    pub fn encryption_keys_mut(&mut self) -> &mut EncryptionKeys {
+        &mut self.encryption
+    }
+
+    /// Checks the signature of the packed message without decoding it.
+    pub fn verify(&self, from: &[u8]) -> Result<Signature> {
+        Ok(self.key_ring.verify(from)?.0)
+    }
+
     #[inline]
     pub fn get_schema(&self, schema_name: &str) -> Option<&Schema> {
-        self.directory.get(&String::from(schema_name))
+        self.directory.get(self.resolve_name(schema_name)?)
     }
 
     #[inline]
-    fn get_record(&self, schema_name: &str) -> Record {
-        let record = Record::new(self.get_schema(schema_name).unwrap()).unwrap();
-        record
-    }
-
-    fn pack_message_into_envelope(&self, schema_name: &str, payload: Value) -> Vec<u8> {
-        let mut envelope = self.get_record(MESSAGE_ENVELOPE_SCHEMA);
-        let inner = to_avro_datum(self.get_schema(schema_name).unwrap(), payload).unwrap();
-        envelope.put("schema", Value::Bytes(schema_name.into()));
-        envelope.put("payload", Value::Bytes(inner));
-        to_avro_datum(self.get_schema(MESSAGE_ENVELOPE_SCHEMA).unwrap(), envelope).unwrap()
-    }
-
-    pub fn read_protocol_message(&self, from: &Vec<u8>) -> Result<(String, Value), String> {
-        let envelope_schema = self.get_schema(MESSAGE_ENVELOPE_SCHEMA).unwrap();
-        let envelope = from_avro_datum(envelope_schema, &mut from.as_slice(), None);
-
-        match envelope {
-            Ok(envelope) => match envelope {
-                Value::Record(fields) => match fields.as_slice() {
-                    [(s_field_name, Value::Bytes(schema)), (p_field_name, Value::Bytes(payload))]
-                        if s_field_name == "schema" && p_field_name == "payload" =>
-                    {
-                        let schema = str::from_utf8(schema.as_slice());
-                        match schema {
-                            Ok(schema_name) => {
-                                let inner_schema = self.get_schema(schema_name);
-
-                                match inner_schema {
-                                        Some(inner_schema) => {
-                                            let inner = from_avro_datum(inner_schema,
-                                                                        &mut payload.clone().as_slice(), None);
-
-                                            match inner {
-                                                Ok(inner) => Ok((String::from(schema_name), inner)),
-                                                _ => Err(String::from("Failed to parse inner AVRO serialized record"))
-                                            }
-                                        }
-                                        _ => Err(format!("No valid schema found in schema catalog for the schema ({}) in serialized record", schema_name))
-                                    }
-                            }
-                            _ => Err(String::from(
-                                "Failed to parse schema name, not a valid UTF-8",
-                            )),
-                        }
+    pub fn get_known_schema(&self, schema_name: &str) -> Result<&Schema> {
+        self.get_schema(schema_name)
+            .ok_or_else(|| ProtocolError::UnknownSchema(String::from(schema_name)))
+    }
+
+    #[inline]
+    pub fn get_record(&self, schema_name: &str) -> Result<Record<'_>> {
+        Record::new(self.get_known_schema(schema_name)?)
+            .ok_or_else(|| ProtocolError::RecordMismatch(String::from(schema_name)))
+    }
+
+    /// Packs the payload into the envelope, sealing it with the key `key_id` if one is given;
+    /// a sealed datum is compressed with `compression` first.
+    pub(crate) fn pack_message_into_envelope(
+        &self,
+        schema_name: &str,
+        payload: Value,
+        compression: &CompressionPolicy,
+        key_id: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let schema_name = self.known_name(schema_name)?;
+        let inner = to_avro_datum(self.get_known_schema(schema_name)?, payload)
+            .map_err(|e| ProtocolError::Encode(format!("{} ({})", e, schema_name)))?;
+        let schema = match self.envelope_format {
+            EnvelopeFormat::SingleObject if key_id.is_some() => {
+                return Err(ProtocolError::Encode(String::from(
+                    "The single-object encoding cannot be sealed",
+                )))
+            }
+            EnvelopeFormat::SingleObject => {
+                return Ok(envelope::write_single_object(
+                    self.fingerprint(schema_name)?,
+                    &inner,
+                ))
+            }
+            EnvelopeFormat::Compact => self.fingerprint(schema_name)?.to_le_bytes().to_vec(),
+            EnvelopeFormat::Named | EnvelopeFormat::Versioned => {
+                Self::file_name(schema_name).into_bytes()
+            }
+            EnvelopeFormat::FullName => schema_name.as_bytes().to_vec(),
+        };
+        let frame = match self.envelope_format {
+            EnvelopeFormat::Versioned => {
+                envelope::write_versioned(self.fingerprint(schema_name)?, &[])
+            }
+            EnvelopeFormat::Compact => envelope::write_compact(&[]),
+            _ => Vec::new(),
+        };
+        let (seal, inner) = match key_id {
+            Some(key_id) => self.seal_payload(compression, key_id, &frame, &schema, inner)?,
+            None => (Vec::new(), inner),
+        };
+        let envelope = self.write_envelope(schema, inner)?;
+        let mut framed = seal;
+        framed.extend(frame);
+        framed.extend(envelope);
+        if self.checksum {
+            Ok(envelope::write_checksummed(&framed))
+        } else {
+            Ok(framed)
+        }
+    }
+
+    /// Compresses the datum as the policy says, then seals it: the sealed payload starts with
+    /// the codec flag, `None` for a datum left as is.
+    fn seal_payload(
+        &self,
+        compression: &CompressionPolicy,
+        key_id: &str,
+        frame: &[u8],
+        schema: &[u8],
+        datum: Vec<u8>,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let (compression, datum) = match compression.apply(&datum)? {
+            Some(compressed) => (compression.compression, compressed),
+            None => (Compression::None, datum),
+        };
+        let mut payload = Vec::with_capacity(1 + datum.len());
+        payload.push(compression.id());
+        payload.extend(datum);
+        self.encryption.seal(key_id, frame, schema, &payload)
+    }
+
+    /// Opens the sealed payload and decompresses the datum it carries.
+    fn open_payload(
+        &self,
+        sealed: &Sealed,
+        frame: &[u8],
+        schema: &[u8],
+        payload: &[u8],
+    ) -> Result<Vec<u8>> {
+        let payload = self.encryption.open(sealed, frame, schema, payload)?;
+        match payload.split_first() {
+            Some((&id, datum)) => Compression::from_id(id)?.decompress(datum, &self.limits),
+            None => Err(ProtocolError::EnvelopeDecode(String::from(
+                "Truncated sealed payload",
+            ))),
+        }
+    }
+
+    fn write_envelope(&self, schema: Vec<u8>, payload: Vec<u8>) -> Result<Vec<u8>> {
+        let mut envelope = self.get_record(MESSAGE_ENVELOPE_SCHEMA)?;
+        envelope.put("schema", Value::Bytes(schema));
+        envelope.put("payload", Value::Bytes(payload));
+        to_avro_datum(self.get_known_schema(MESSAGE_ENVELOPE_SCHEMA)?, envelope)
+            .map_err(|e| ProtocolError::Encode(format!("{} ({})", e, MESSAGE_ENVELOPE_SCHEMA)))
+    }
+
+    /// Frames the message, compressing it as the policy says and prefixing the envelope with
+    /// the headers unless they are empty.
+    pub(crate) fn pack_message(&self, message: ProtocolMessage) -> Result<Vec<u8>> {
+        let key_id = self.schema_key_id(&message.schema);
+        self.pack_sealed_message(message, key_id)
+    }
+
+    /// Packs the message sealed with the key `key_id`, say the key of its stream, in place of
+    /// the key its schema is sealed with.
+    pub(crate) fn pack_sealed_message(
+        &self,
+        message: ProtocolMessage,
+        key_id: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let framed = self.frame_message(message, &self.compression, key_id)?;
+        if self.envelope_format == EnvelopeFormat::SingleObject {
+            return Ok(framed);
+        }
+        Ok(self.sign(framed))
+    }
+
+    /// The key the payloads of the schema are sealed with, whatever name it is given by.
+    fn schema_key_id(&self, schema_name: &str) -> Option<&str> {
+        let schema_name = self.resolve_name(schema_name).unwrap_or(schema_name);
+        self.encryption.key_for(schema_name)
+    }
+
+    fn sign(&self, framed: Vec<u8>) -> Vec<u8> {
+        match &self.signing_key {
+            Some(key) => key.sign(&framed),
+            None => framed,
+        }
+    }
+
+    fn frame_message(
+        &self,
+        message: ProtocolMessage,
+        compression: &CompressionPolicy,
+        key_id: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let mut framed =
+            self.pack_message_into_envelope(&message.schema, message.object, compression, key_id)?;
+        // A sealed datum is compressed before it is sealed, the ciphertext would not shrink.
+        if self.envelope_format != EnvelopeFormat::SingleObject && key_id.is_none() {
+            framed = Self::compress(compression, framed)?;
+        }
+        if message.headers.is_empty() {
+            return Ok(framed);
+        }
+        if self.envelope_format == EnvelopeFormat::SingleObject {
+            return Err(ProtocolError::Encode(String::from(
+                "The single-object encoding cannot carry headers",
+            )));
+        }
+        let headers = to_avro_datum(&self.headers_schema, message.headers.to_avro())
+            .map_err(|e| ProtocolError::Encode(format!("{} (headers)", e)))?;
+        Ok(envelope::write_headers(&headers, &framed))
+    }
+
+    fn compress(compression: &CompressionPolicy, framed: Vec<u8>) -> Result<Vec<u8>> {
+        match compression.apply(&framed)? {
+            Some(compressed) => Ok(envelope::write_compressed(
+                compression.compression,
+                &compressed,
+            )),
+            None => Ok(framed),
+        }
+    }
+
+    fn decompress<'a>(&self, from: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match envelope::strip_compressed(from)? {
+            Some((compression, data)) => {
+                Ok(Cow::Owned(compression.decompress(data, &self.limits)?))
+            }
+            None => Ok(Cow::Borrowed(from)),
+        }
+    }
+
+    /// Packs the messages, possibly of different schemas, into one `MessageBatch` frame which
+    /// is compressed as a whole.
+    pub(crate) fn pack_batch(&self, messages: Vec<ProtocolMessage>) -> Result<Vec<u8>> {
+        let items = messages
+            .into_iter()
+            .map(|m| {
+                let key_id = self.schema_key_id(&m.schema);
+                self.frame_message(m, &CompressionPolicy::default(), key_id)
+                    .map(Value::Bytes)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let batch = Value::Record(vec![("messages".into(), Value::Array(items))]);
+        let mut framed = envelope::BATCH_MAGIC.to_vec();
+        framed.extend(
+            to_avro_datum(&self.batch_schema, batch)
+                .map_err(|e| ProtocolError::Encode(format!("{} (batch)", e)))?,
+        );
+        Ok(self.sign(Self::compress(&self.compression, framed)?))
+    }
+
+    /// Unpacks the `MessageBatch` frame; a single message is read as a batch of one.
+    pub fn read_batch(&self, from: &[u8]) -> Result<Vec<ProtocolMessage>> {
+        self.limits.check_message_size(from.len())?;
+        let (signature, signed) = self.check_signature(from)?;
+        let from = self.decompress(signed)?;
+        let mut batch = match envelope::strip_batch(&from) {
+            Some(batch) => batch,
+            None => return Ok(vec![self.read_signed(&from, signature)?]),
+        };
+        self.limits.check_datum(&self.batch_schema, batch)?;
+        let batch = from_avro_datum(&self.batch_schema, &mut batch, None)
+            .map_err(|e| ProtocolError::EnvelopeDecode(format!("{} (batch)", e)))?;
+        let fields = RecordFields::new(MESSAGE_BATCH_SCHEMA, &batch)?;
+        fields
+            .array::<Vec<u8>>("messages")?
+            .iter()
+            .map(|item| self.read_signed(item, signature.clone()))
+            .collect()
+    }
+
+    pub fn read_message(&self, from: &[u8]) -> Result<ProtocolMessage> {
+        self.limits.check_message_size(from.len())?;
+        let (signature, signed) = self.check_signature(from)?;
+        self.read_signed(signed, signature)
+    }
+
+    /// The outcome of the signature check and the signed message, if the policy accepts it.
+    fn check_signature<'a>(&self, from: &'a [u8]) -> Result<(Signature, &'a [u8])> {
+        let (signature, signed) = self.key_ring.verify(from)?;
+        self.verify_policy.enforce(&signature)?;
+        Ok((signature, signed))
+    }
+
+    /// Decodes the message found under the signature.
+    fn read_signed(&self, from: &[u8], signature: Signature) -> Result<ProtocolMessage> {
+        let (headers, framed) = self.split_headers(from)?;
+        let (schema, object) = self.read_framed(&self.decompress(framed)?)?;
+        Ok(ProtocolMessage {
+            schema,
+            object,
+            headers,
+            signature,
+        })
+    }
+
+    /// The headers and the framed message following them.
+    fn split_headers<'a>(&self, from: &'a [u8]) -> Result<(Headers, &'a [u8])> {
+        match envelope::strip_headers(from) {
+            Some(mut rest) => {
+                self.limits.check_datum(&self.headers_schema, rest)?;
+                let headers = from_avro_datum(&self.headers_schema, &mut rest, None)
+                    .map_err(|e| ProtocolError::EnvelopeDecode(format!("{} (headers)", e)))?;
+                Ok((Headers::from_avro(&headers, &self.headers_schema)?, rest))
+            }
+            None => Ok((Headers::default(), from)),
+        }
+    }
+
+    /// Renders the message as a JSON document holding its schema name, the object in the Avro
+    /// JSON encoding and the headers unless they are empty.
+    pub fn message_to_json(&self, message: &ProtocolMessage) -> Result<String> {
+        let schema_name = self.known_name(&message.schema)?;
+        let schema = self.get_known_schema(schema_name)?;
+        let mut document = serde_json::Map::new();
+        document.insert("schema".into(), schema_name.into());
+        document.insert("object".into(), value_to_json(&message.object, schema)?);
+        if !message.headers.is_empty() {
+            document.insert(
+                "headers".into(),
+                value_to_json(&message.headers.to_avro(), &self.headers_schema)?,
+            );
+        }
+        Ok(serde_json::Value::Object(document).to_string())
+    }
+
+    /// Parses the JSON document written by `message_to_json`.
+    pub fn message_from_json(&self, json: &str) -> Result<ProtocolMessage> {
+        let invalid = |reason: &str| ProtocolError::EnvelopeDecode(format!("{} (JSON)", reason));
+        let document: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+        let schema_name = document
+            .get("schema")
+            .and_then(|s| s.as_str())
+            .ok_or_else(|| invalid("No schema name"))?;
+        let schema_name = self.known_name(schema_name)?;
+        let object = document.get("object").ok_or_else(|| invalid("No object"))?;
+        let mut message = ProtocolMessage::new(
+            schema_name,
+            json_to_value(object, self.get_known_schema(schema_name)?)?,
+        );
+        if let Some(headers) = document.get("headers") {
+            let headers = json_to_value(headers, &self.headers_schema)?;
+            message.headers = Headers::from_avro(&headers, &self.headers_schema)?;
+        }
+        Ok(message)
+    }
+
+    /// Packs the message given as the JSON document of `message_to_json`.
+    pub fn json_to_bytes(&self, json: &str) -> Result<Vec<u8>> {
+        self.pack_message(self.message_from_json(json)?)
+    }
+
+    /// Renders the packed message as the JSON document of `message_to_json`.
+    pub fn bytes_to_json(&self, from: &[u8]) -> Result<String> {
+        self.message_to_json(&self.read_message(from)?)
+    }
+
+    /// Decodes the message, dropping its headers.
+    pub fn read_protocol_message(&self, from: &[u8]) -> Result<(String, Value)> {
+        let message = self.read_message(from)?;
+        Ok((message.schema, message.object))
+    }
+
+    /// The schema of the packed message as its framing names it, without checking the
+    /// signature or opening and decoding the payload. A name unknown to the catalog is
+    /// returned as carried in the envelope; a batch is named `MessageBatch`.
+    pub fn peek_schema(&self, from: &[u8]) -> Result<String> {
+        self.limits.check_message_size(from.len())?;
+        let signed = signing::strip_signature(from)?;
+        let (_, framed) = self.split_headers(signed)?;
+        let from = self.decompress(framed)?;
+        if envelope::strip_batch(&from).is_some() {
+            return Ok(String::from(MESSAGE_BATCH_SCHEMA));
+        }
+        let from = envelope::verify_checksum(&from)?;
+        let framed = encryption::strip_sealed(from)?.map_or(from, |(_, framed)| framed);
+        match envelope::detect_framing(framed)? {
+            Framing::Named(envelope) | Framing::Versioned { envelope, .. } => {
+                let (schema, _) = self.read_envelope(envelope, None)?;
+                let schema_name = String::from_utf8(schema).map_err(|_| {
+                    ProtocolError::EnvelopeDecode(String::from(
+                        "Failed to parse schema name, not a valid UTF-8",
+                    ))
+                })?;
+                Ok(self
+                    .resolve_name(&schema_name)
+                    .map(String::from)
+                    .unwrap_or(schema_name))
+            }
+            Framing::Compact(envelope) => {
+                let (schema, _) = self.read_envelope(envelope, None)?;
+                self.schema_name_by_fingerprint(envelope::read_fingerprint(&schema)?)
+            }
+            Framing::SingleObject { fingerprint, .. } => {
+                self.schema_name_by_fingerprint(fingerprint)
+            }
+        }
+    }
+
+    fn read_framed(&self, from: &[u8]) -> Result<(String, Value)> {
+        let from = envelope::verify_checksum(from)?;
+        let (sealed, framed) = match encryption::strip_sealed(from)? {
+            Some((sealed, framed)) => (Some(sealed), framed),
+            None => (None, from),
+        };
+        let sealed = sealed.as_ref();
+        // The seal covers the frame header preceding the envelope along with the envelope.
+        let seal = |envelope: &[u8]| {
+            let frame = &framed[..framed.len() - envelope.len()];
+            sealed.map(|sealed| (sealed, frame))
+        };
+        match envelope::detect_framing(framed)? {
+            Framing::Named(envelope) => {
+                let (schema, payload) = self.read_envelope(envelope, seal(envelope))?;
+                let schema_name = self.read_schema_name(schema)?;
+                let inner_schema = self.get_known_schema(&schema_name)?;
+                let inner = self.read_payload(&schema_name, inner_schema, None, &payload)?;
+                Ok((schema_name, inner))
+            }
+            Framing::Versioned {
+                fingerprint,
+                envelope,
+            } => {
+                let (schema, payload) = self.read_envelope(envelope, seal(envelope))?;
+                let schema_name = self.read_schema_name(schema)?;
+                let inner = self.read_payload_version(&schema_name, fingerprint, &payload)?;
+                Ok((schema_name, inner))
+            }
+            Framing::Compact(envelope) => {
+                let (schema, payload) = self.read_envelope(envelope, seal(envelope))?;
+                let fingerprint = envelope::read_fingerprint(&schema)?;
+                let schema_name = self.schema_name_by_fingerprint(fingerprint)?;
+                let inner = self.read_payload_version(&schema_name, fingerprint, &payload)?;
+                Ok((schema_name, inner))
+            }
+            Framing::SingleObject { .. } if sealed.is_some() => Err(ProtocolError::EnvelopeDecode(
+                String::from("The single-object encoding cannot be sealed"),
+            )),
+            Framing::SingleObject { fingerprint, datum } => {
+                let schema_name = self.schema_name_by_fingerprint(fingerprint)?;
+                let inner = self.read_payload_version(&schema_name, fingerprint, datum)?;
+                Ok((schema_name, inner))
+            }
+        }
+    }
+
+    fn schema_name_by_fingerprint(&self, fingerprint: u64) -> Result<String> {
+        self.versions
+            .get(&fingerprint)
+            .map(|(name, _)| name.clone())
+            .ok_or(ProtocolError::UnknownFingerprint(fingerprint))
+    }
+
+    /// The full name the schema name carried in the envelope resolves to.
+    fn read_schema_name(&self, schema: Vec<u8>) -> Result<String> {
+        let schema_name = String::from_utf8(schema).map_err(|_| {
+            ProtocolError::EnvelopeDecode(String::from(
+                "Failed to parse schema name, not a valid UTF-8",
+            ))
+        })?;
+        Ok(String::from(self.known_name(&schema_name)?))
+    }
+
+    /// Splits the `MessageEnvelope` into the schema identifier and the undecoded payload, opened
+    /// if it is sealed with the frame header preceding the envelope.
+    fn read_envelope(
+        &self,
+        from: &[u8],
+        sealed: Option<(&Sealed, &[u8])>,
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let envelope_schema = self.get_known_schema(MESSAGE_ENVELOPE_SCHEMA)?;
+        self.limits.check_datum(envelope_schema, from)?;
+        let mut reader = from;
+        let envelope = from_avro_datum(envelope_schema, &mut reader, None)
+            .map_err(|e| ProtocolError::EnvelopeDecode(e.to_string()))?;
+
+        let fields = match envelope {
+            Value::Record(fields) => fields,
+            _ => {
+                return Err(ProtocolError::EnvelopeDecode(String::from(
+                    "Failed to parse/match outer AVRO Record",
+                )))
+            }
+        };
+
+        match <[(String, Value); 2]>::try_from(fields) {
+            Ok([(s_field_name, Value::Bytes(schema)), (p_field_name, Value::Bytes(payload))])
+                if s_field_name == "schema" && p_field_name == "payload" =>
+            {
+                match sealed {
+                    Some((sealed, frame)) => {
+                        let payload = self.open_payload(sealed, frame, &schema, &payload)?;
+                        Ok((schema, payload))
                     }
-                    _ => Err(String::from(
-                        "No outer AVRO record (MessageEnvelope) matched",
-                    )),
-                },
-                _ => Err(String::from("Failed to parse/match outer AVRO Record")),
-            },
-            _ => Err(String::from("Failed to deserialize the outer message")),
+                    None => Ok((schema, payload)),
+                }
+            }
+            _ => Err(ProtocolError::EnvelopeDecode(String::from(
+                "No outer AVRO record (MessageEnvelope) matched",
+            ))),
         }
     }
+
+    /// Decodes the payload written with the version `fingerprint` of the schema into its
+    /// current version.
+    fn read_payload_version(
+        &self,
+        schema_name: &str,
+        fingerprint: u64,
+        payload: &[u8],
+    ) -> Result<Value> {
+        let reader_schema = self.get_known_schema(schema_name)?;
+        let writer_schema = self.get_schema_version(schema_name, fingerprint)?;
+        let reader_schema =
+            (self.fingerprint(schema_name)? != fingerprint).then_some(reader_schema);
+        self.read_payload(schema_name, writer_schema, reader_schema, payload)
+    }
+
+    /// Decodes the payload written with `writer_schema`, resolving it into `reader_schema`.
+    fn read_payload(
+        &self,
+        schema_name: &str,
+        writer_schema: &Schema,
+        reader_schema: Option<&Schema>,
+        payload: &[u8],
+    ) -> Result<Value> {
+        self.limits.check_datum(writer_schema, payload)?;
+        from_avro_datum(writer_schema, &mut &payload[..], reader_schema).map_err(|e| {
+            ProtocolError::PayloadDecode {
+                schema: String::from(schema_name),
+                reason: e.to_string(),
+            }
+        })
+    }
 }
 
+#[cfg(feature = "python")]
+#[derive(Default)]
 #[pyclass]
 pub struct Builder {
-    builder: BuilderImpl,
+    codec: Codec,
+    classes: HashMap<String, PyObject>,
 }
 
-#[derive(Clone)]
-#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct ProtocolMessage {
     pub schema: String,
     pub object: Value,
+    pub headers: Headers,
+    /// Outcome of the signature check of the received message.
+    pub signature: Signature,
 }
 
+impl ProtocolMessage {
+    pub fn new(schema: &str, object: Value) -> Self {
+        ProtocolMessage {
+            schema: String::from(schema),
+            object,
+            headers: Headers::default(),
+            signature: Signature::default(),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ProtocolMessage {
+    #[getter]
+    fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    #[getter]
+    fn headers(&self) -> Headers {
+        self.headers.clone()
+    }
+
+    #[setter]
+    fn set_headers(&mut self, headers: Headers) {
+        self.headers = headers;
+    }
+
+    #[getter]
+    fn signature(&self) -> Signature {
+        self.signature.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+impl Default for BuilderImpl {
+    fn default() -> Self {
+        BuilderImpl::embedded().expect("Embedded schemas must be valid")
+    }
+}
+
+#[cfg(feature = "python")]
 #[pymethods]
 impl Builder {
+    /// Uses the embedded schemas unless `path_prefix` points to a schema tree on disk.
     #[new]
-    pub fn new(path_prefix: &str) -> Builder {
-        Builder {
-            builder: BuilderImpl::new(path_prefix),
-        }
+    #[pyo3(signature = (path_prefix = None))]
+    pub fn new(path_prefix: Option<&str>) -> PyResult<Builder> {
+        let builder = match path_prefix {
+            Some(path_prefix) => BuilderImpl::new(path_prefix)?,
+            None => BuilderImpl::embedded()?,
+        };
+        Ok(Builder {
+            codec: Codec::from(builder),
+            classes: HashMap::default(),
+        })
     }
 
-    pub fn load_to_avro(&self, obj: Vec<u8>) -> Option<ProtocolMessage> {
-        match self.builder.read_protocol_message(&obj) {
-            Ok((schema, object)) => Some(ProtocolMessage { schema, object }),
-            Err(m) => {
-                warn!(
-                    "Unable to decode the message from the envelope. Error is {}",
-                    m
-                );
-                None
-            }
+    /// Loads the schemas found under `root` and returns the builder with the report of the
+    /// scan; the files which fail to load are left out instead of failing the builder.
+    #[staticmethod]
+    pub fn discover(py: Python, root: &str) -> PyResult<(Builder, SchemaScan)> {
+        let (codec, scan) = py.allow_threads(|| Codec::discover(root))?;
+        Ok((
+            Builder {
+                codec,
+                classes: HashMap::default(),
+            },
+            scan,
+        ))
+    }
+
+    /// Adds the `.avsc` schema to the catalog and returns its name.
+    pub fn register_schema(&mut self, json: &str) -> PyResult<String> {
+        Ok(self.codec.register_schema(json)?)
+    }
+
+    /// Adds another version of a known schema and returns its fingerprint.
+    pub fn register_schema_version(&mut self, json: &str) -> PyResult<u64> {
+        Ok(self.codec.register_schema_version(json)?)
+    }
+
+    /// Lists the schemas the builder understands.
+    pub fn schemas(&self) -> PyResult<Vec<SchemaInfo>> {
+        Ok(self.codec.builder().schemas()?)
+    }
+
+    /// Describes the schema along with its fields or symbols.
+    pub fn schema_info(&self, name: &str) -> PyResult<SchemaInfo> {
+        Ok(self.codec.builder().schema_info(name)?)
+    }
+
+    /// Returns the canonical JSON of the schema.
+    pub fn schema_json(&self, name: &str) -> PyResult<String> {
+        Ok(self.codec.builder().schema_json(name)?)
+    }
+
+    /// Compresses the messages of `threshold` bytes and larger with `compression`.
+    #[pyo3(signature = (compression, threshold = 0))]
+    pub fn set_compression(&mut self, compression: Compression, threshold: usize) {
+        self.codec
+            .set_compression(CompressionPolicy::new(compression, threshold))
+    }
+
+    #[getter]
+    pub fn checksum(&self) -> bool {
+        self.codec.builder().checksum()
+    }
+
+    #[setter]
+    pub fn set_checksum(&mut self, checksum: bool) {
+        self.codec.set_checksum(checksum)
+    }
+
+    #[getter]
+    pub fn decode_limits(&self) -> DecodeLimits {
+        self.codec.builder().decode_limits()
+    }
+
+    #[setter]
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.codec.set_decode_limits(limits)
+    }
+
+    /// Key id the outgoing messages are signed with, `None` if they are sent unsigned.
+    #[getter]
+    pub fn signing_key_id(&self) -> Option<String> {
+        let key = self.codec.builder().signing_key()?;
+        Some(String::from(key.key_id()))
+    }
+
+    /// Signs the outgoing messages with HMAC-SHA256 under `key_id`.
+    pub fn set_signing_key(&mut self, key_id: &str, secret: Vec<u8>) -> PyResult<()> {
+        let key = SigningKey::new(key_id, &secret)?;
+        self.codec.set_signing_key(Some(key));
+        Ok(())
+    }
+
+    pub fn clear_signing_key(&mut self) {
+        self.codec.set_signing_key(None)
+    }
+
+    /// Trusts the signatures made with the key `key_id`.
+    pub fn add_verify_key(&mut self, key_id: &str, secret: Vec<u8>) -> PyResult<()> {
+        Ok(self.codec.key_ring_mut().insert(key_id, &secret)?)
+    }
+
+    pub fn remove_verify_key(&mut self, key_id: &str) -> bool {
+        self.codec.key_ring_mut().remove(key_id)
+    }
+
+    #[getter]
+    pub fn verify_policy(&self) -> VerifyPolicy {
+        self.codec.builder().verify_policy()
+    }
+
+    #[setter]
+    pub fn set_verify_policy(&mut self, policy: VerifyPolicy) {
+        self.codec.set_verify_policy(policy)
+    }
+
+    /// Checks the signature of the message without decoding it.
+    pub fn verify(&self, py: Python, message: Vec<u8>) -> PyResult<Signature> {
+        let builder = self.codec.builder();
+        Ok(py.allow_threads(|| builder.verify(&message))?)
+    }
+
+    /// Adds the 32-byte ChaCha20-Poly1305 key; the payloads sealed with it are opened.
+    pub fn add_encryption_key(&mut self, key_id: &str, key: Vec<u8>) -> PyResult<()> {
+        Ok(self.codec.encryption_keys_mut().insert(key_id, &key)?)
+    }
+
+    /// Removes the key unless it is current or picked for a schema.
+    pub fn remove_encryption_key(&mut self, key_id: &str) -> PyResult<bool> {
+        Ok(self.codec.encryption_keys_mut().remove(key_id)?)
+    }
+
+    /// Key id the payloads are sealed with, `None` to send them in clear text.
+    #[getter]
+    pub fn encryption_key_id(&self) -> Option<String> {
+        self.codec
+            .builder()
+            .encryption_keys()
+            .current()
+            .map(String::from)
+    }
+
+    #[setter]
+    pub fn set_encryption_key_id(&mut self, key_id: Option<&str>) -> PyResult<()> {
+        Ok(self.codec.encryption_keys_mut().set_current(key_id)?)
+    }
+
+    /// Seals the payloads of the schema with the key `key_id` in place of the current one.
+    #[pyo3(signature = (schema, key_id = None))]
+    pub fn set_schema_encryption_key(
+        &mut self,
+        schema: &str,
+        key_id: Option<&str>,
+    ) -> PyResult<()> {
+        let builder = self.codec.builder();
+        let schema = String::from(builder.resolve_name(schema).unwrap_or(schema));
+        Ok(self
+            .codec
+            .encryption_keys_mut()
+            .set_schema_key(&schema, key_id)?)
+    }
+
+    #[getter]
+    pub fn envelope_format(&self) -> EnvelopeFormat {
+        self.codec.builder().envelope_format()
+    }
+
+    #[setter]
+    pub fn set_envelope_format(&mut self, format: EnvelopeFormat) {
+        self.codec.set_envelope_format(format)
+    }
+
+    /// Registers the class which provides the `SCHEMA` attribute, the `to_avro()` method
+    /// returning the record as a dict and the `from_avro(dict)` classmethod.
+    pub fn register_class(&mut self, cls: &PyAny) -> PyResult<String> {
+        let schema: String = cls.getattr("SCHEMA")?.extract()?;
+        if !cls.hasattr("to_avro")? || !cls.hasattr("from_avro")? {
+            return Err(PyTypeError::new_err(format!(
+                "Class {} must provide to_avro and from_avro",
+                cls.repr()?
+            )));
         }
+        let schema = String::from(self.codec.builder().known_name(&schema)?);
+        self.codec.registry_mut().register(
+            &schema,
+            MessageHandler {
+                extract: Some(python::extract_custom),
+                ..MessageHandler::raw()
+            },
+        );
+        self.classes.insert(schema.clone(), cls.into());
+        Ok(schema)
     }
 
-    pub fn save_from_avro(&self, message: ProtocolMessage) -> Vec<u8> {
-        self.builder
-            .pack_message_into_envelope(message.schema.as_str(), message.object)
+    pub fn load_to_avro(&self, py: Python, obj: Vec<u8>) -> PyResult<ProtocolMessage> {
+        let codec = &self.codec;
+        Ok(py.allow_threads(|| codec.decode_message(&obj))?)
     }
 
-    pub fn save(&self, obj: &PyAny) -> Option<Vec<u8>> {
-        fn try_to<T: Clone + PyClass + ToProtocolMessage>(
-            mb: &Builder,
-            x: &PyAny,
-        ) -> Option<Vec<u8>> {
-            if x.is_instance_of::<T>().unwrap() {
-                let ro: T = x.extract().unwrap();
-                let protocol_message_res = ro.save(mb);
+    pub fn save_from_avro(&self, py: Python, message: ProtocolMessage) -> PyResult<Vec<u8>> {
+        let codec = &self.codec;
+        Ok(py.allow_threads(|| codec.encode_message(message))?)
+    }
 
-                protocol_message_res.map(|m| mb.save_from_avro(m))
-            } else {
-                None
+    /// Seals the payload with the key `key_id`, say the key of its stream, in place of the key
+    /// its schema is sealed with.
+    #[pyo3(signature = (obj, headers = None, key_id = None))]
+    pub fn save(
+        &self,
+        py: Python,
+        obj: &PyAny,
+        headers: Option<Headers>,
+        key_id: Option<&str>,
+    ) -> PyResult<Vec<u8>> {
+        let message = self.extract_message(obj)?;
+        let codec = &self.codec;
+        Ok(py.allow_threads(|| {
+            let mut message = message.save(codec.builder())?;
+            message.headers = headers.unwrap_or_default();
+            match key_id {
+                Some(key_id) => codec.encode_sealed_message(message, key_id),
+                None => codec.encode_message(message),
             }
-        }
+        })?)
+    }
 
-        try_to::<UnitElementMessage>(self, obj)
-            .or_else(|| try_to::<NotifyMessage>(self, obj))
-            .or_else(|| try_to::<PingRequestResponse>(self, obj))
-            .or_else(|| try_to::<ServicesFFProbeRequest>(self, obj))
-            .or_else(|| try_to::<ServicesFFProbeResponse>(self, obj))
-            .or_else(|| try_to::<StreamTrackUnitElementsRequest>(self, obj))
-            .or_else(|| try_to::<StreamTrackUnitElementsResponse>(self, obj))
-            .or_else(|| try_to::<StreamTracksRequest>(self, obj))
-            .or_else(|| try_to::<StreamTracksResponse>(self, obj))
-            .or_else(|| try_to::<StreamTrackUnitsRequest>(self, obj))
-            .or_else(|| try_to::<StreamTrackUnitsResponse>(self, obj))
-            .or_else(|| try_to::<KeepAliveMessage>(self, obj))
-    }
-
-    pub fn load(&self, message: Vec<u8>) -> Option<PyObject> {
-        fn try_from<T: FromProtocolMessage + PyClass + Into<PyClassInitializer<T>>>(
-            message: &ProtocolMessage,
-        ) -> Option<PyObject> {
-            let gil = Python::acquire_gil();
-            let py = gil.python();
-
-            T::load(message).map(|o| Py::new(py, o).unwrap().to_object(py))
-        }
+    /// Packs the objects, possibly of different types, into one batch frame.
+    pub fn save_batch(&self, py: Python, objs: Vec<&PyAny>) -> PyResult<Vec<u8>> {
+        let messages = objs
+            .into_iter()
+            .map(|obj| self.extract_message(obj))
+            .collect::<PyResult<Vec<_>>>()?;
+        let codec = &self.codec;
+        Ok(py.allow_threads(|| codec.encode_batch(&messages))?)
+    }
 
-        match self.load_to_avro(message) {
-            None => None,
-            Some(obj) => try_from::<UnitElementMessage>(&obj)
-                .or_else(|| try_from::<NotifyMessage>(&obj))
-                .or_else(|| try_from::<PingRequestResponse>(&obj))
-                .or_else(|| try_from::<ServicesFFProbeRequest>(&obj))
-                .or_else(|| try_from::<ServicesFFProbeResponse>(&obj))
-                .or_else(|| try_from::<StreamTrackUnitElementsRequest>(&obj))
-                .or_else(|| try_from::<StreamTrackUnitElementsResponse>(&obj))
-                .or_else(|| try_from::<StreamTracksRequest>(&obj))
-                .or_else(|| try_from::<StreamTracksResponse>(&obj))
-                .or_else(|| try_from::<StreamTrackUnitsRequest>(&obj))
-                .or_else(|| try_from::<StreamTrackUnitsResponse>(&obj))
-                .or_else(|| try_from::<KeepAliveMessage>(&obj)),
-        }
+    /// Renders the protocol object as a JSON document, see `json_to_bytes`.
+    pub fn to_json(&self, py: Python, obj: &PyAny) -> PyResult<String> {
+        let message = self.extract_message(obj)?;
+        let builder = self.codec.builder();
+        Ok(py.allow_threads(|| message.to_json(builder))?)
+    }
+
+    pub fn from_json(&self, py: Python, json: &str) -> PyResult<PyObject> {
+        let codec = &self.codec;
+        let message = py.allow_threads(|| codec.decode_json(json))?;
+        self.message_into_py(py, message)
+    }
+
+    /// Packs the message given as `{"schema": ..., "object": ..., "headers": ...}` with the
+    /// object and the headers in the Avro JSON encoding.
+    pub fn json_to_bytes(&self, py: Python, json: &str) -> PyResult<Vec<u8>> {
+        let builder = self.codec.builder();
+        Ok(py.allow_threads(|| builder.json_to_bytes(json))?)
+    }
+
+    pub fn bytes_to_json(&self, py: Python, message: Vec<u8>) -> PyResult<String> {
+        let builder = self.codec.builder();
+        Ok(py.allow_threads(|| builder.bytes_to_json(&message))?)
+    }
+
+    pub fn load(&self, py: Python, message: Vec<u8>) -> PyResult<PyObject> {
+        let codec = &self.codec;
+        let message = py.allow_threads(|| codec.decode(&message))?;
+        self.message_into_py(py, message)
+    }
+
+    /// Loads the message along with the headers of its envelope.
+    pub fn load_with_headers(&self, py: Python, message: Vec<u8>) -> PyResult<(PyObject, Headers)> {
+        let codec = &self.codec;
+        let (message, headers) = py.allow_threads(|| codec.decode_with_headers(&message))?;
+        Ok((self.message_into_py(py, message)?, headers))
+    }
+
+    /// Loads every message of the batch frame; a single message gives a list of one.
+    pub fn load_batch(&self, py: Python, batch: Vec<u8>) -> PyResult<Vec<PyObject>> {
+        let codec = &self.codec;
+        let messages = py.allow_threads(|| codec.decode_batch(&batch))?;
+        messages
+            .into_iter()
+            .map(|m| self.message_into_py(py, m))
+            .collect()
     }
 }
 
+#[cfg(feature = "python")]
 impl Builder {
-    pub fn get_record(&self, schema_name: &str) -> Record {
-        let record = Record::new(self.builder.get_schema(schema_name).unwrap()).unwrap();
-        record
+    /// Extracts the protocol object; it is converted to Avro once the GIL is released.
+    fn extract_message(&self, obj: &PyAny) -> PyResult<AnyMessage> {
+        let schema = obj
+            .getattr("SCHEMA")
+            .and_then(|s| s.extract::<String>())
+            .ok();
+        let builder = self.codec.builder();
+        let registry = self.codec.registry();
+        match schema
+            .as_deref()
+            .map(|s| builder.resolve_name(s).unwrap_or(s))
+            .and_then(|s| Some((s, registry.handler(s)?)))
+        {
+            Some((
+                schema,
+                MessageHandler {
+                    extract: Some(extract),
+                    ..
+                },
+            )) => extract(obj, schema),
+            Some((schema, _)) => Err(ProtocolError::NoDecoder(String::from(schema)).into()),
+            None => Err(PyTypeError::new_err(format!(
+                "Unsupported protocol object type: {}",
+                obj.get_type().name()?
+            ))),
+        }
+    }
+
+    fn message_into_py(&self, py: Python, message: AnyMessage) -> PyResult<PyObject> {
+        match message.into_builtin_py(py) {
+            Ok(obj) => Ok(obj),
+            Err(custom) => {
+                let message = custom.save(self.codec.builder())?;
+                match self.classes.get(&message.schema) {
+                    Some(cls) => cls.call_method1(
+                        py,
+                        "from_avro",
+                        (python::value_to_py(py, &message.object)?,),
+                    ),
+                    None => Ok(message.into_py(py)),
+                }
+            }
+        }
+    }
+
+    pub fn get_record(&self, schema_name: &str) -> Result<Record<'_>> {
+        self.codec.builder().get_record(schema_name)
+    }
+
+    pub(crate) fn builder_impl(&self) -> &BuilderImpl {
+        self.codec.builder()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::{Builder, UNIT_ELEMENT_MESSAGE_SCHEMA};
+    use crate::avro::{
+        BuilderImpl, KEEPALIVE_MESSAGE_SCHEMA, MESSAGE_ENVELOPE_SCHEMA,
+        UNIT_ELEMENT_MESSAGE_SCHEMA, UNIT_SCHEMA,
+    };
+    use crate::compression::CompressionPolicy;
+    use crate::envelope::EnvelopeFormat;
+    use crate::error::ProtocolError;
+    use crate::record::RecordFields;
     use crate::utils::get_avro_path;
+    use avro_rs::types::Value;
+    use avro_rs::{from_avro_datum, to_avro_datum};
+    use std::collections::HashMap;
 
     #[test]
     fn test_load_schemas() {
-        let mb = Builder::new(get_avro_path().as_str());
-        let _r = mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA);
+        let mb = BuilderImpl::new(get_avro_path().as_str()).unwrap();
+        let _r = mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA).unwrap();
+    }
+
+    #[test]
+    fn test_embedded_schemas() {
+        let embedded = BuilderImpl::embedded().unwrap();
+        let loaded = BuilderImpl::new(get_avro_path().as_str()).unwrap();
+        assert_eq!(embedded.directory, loaded.directory);
+
+        let mb = BuilderImpl::default();
+        let _r = mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA).unwrap();
+    }
+
+    #[test]
+    fn test_missing_schemas() {
+        let res = BuilderImpl::new("/nonexistent");
+        assert!(matches!(res, Err(ProtocolError::SchemaLoad(_))));
+    }
+
+    #[test]
+    fn test_discover_schemas() {
+        let root = std::env::temp_dir().join(format!("protocol-discover-{}", std::process::id()));
+        let nested = root.join("insight/custom");
+        std::fs::create_dir_all(&nested).unwrap();
+        let write = |file: &str, json: &str| std::fs::write(nested.join(file), json).unwrap();
+        write(
+            "a.avsc",
+            r#"{"type": "record", "name": "Reading", "namespace": "insight.custom",
+                "fields": [{"name": "unit", "type": "insight.custom.Scale"},
+                           {"name": "track_type", "type": "insight.storage.TrackType"}]}"#,
+        );
+        write(
+            "b.avsc",
+            r#"{"type": "enum", "name": "Scale", "namespace": "insight.custom", "symbols": ["C"]}"#,
+        );
+        write("broken.avsc", "{");
+        write(
+            "orphan.avsc",
+            r#"{"type": "record", "name": "Orphan", "namespace": "insight.custom",
+                "fields": [{"name": "missing", "type": "Missing"}]}"#,
+        );
+        write(
+            "bad.avsc",
+            r#"{"type": "enum", "name": "Bad", "namespace": "insight.custom", "symbols": [1]}"#,
+        );
+        write(
+            "uses_bad.avsc",
+            r#"{"type": "record", "name": "UsesBad", "namespace": "insight.custom",
+                "fields": [{"name": "bad", "type": "Bad"}]}"#,
+        );
+        write("notes.txt", "not a schema");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&root, nested.join("loop")).unwrap();
+
+        let result = BuilderImpl::discover(root.to_str().unwrap());
+        std::fs::remove_dir_all(&root).unwrap();
+        let (mb, scan) = result.unwrap();
+        let loaded = scan
+            .loaded
+            .iter()
+            .map(|p| p.rsplit('/').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, ["b.avsc", "a.avsc"]);
+        assert_eq!(scan.embedded.len(), BuilderImpl::schema_files().len());
+        assert_eq!(scan.failures.len(), 4);
+        assert!(scan.failures[0].path.ends_with("bad.avsc"));
+        assert!(scan.failures[1].path.ends_with("broken.avsc"));
+        assert!(scan.failures[1].reason.starts_with("Invalid JSON"));
+        assert_eq!(
+            scan.failures[2].reason,
+            "Unresolved reference (insight.custom.Missing)"
+        );
+        assert_eq!(
+            scan.failures[3].reason,
+            "Unresolved reference (insight.custom.Bad)"
+        );
+        assert!(mb.schema_info("insight.custom.Reading").is_ok());
+        assert!(mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA).is_ok());
+        assert!(mb.schema_info("insight.custom.Orphan").is_err());
+    }
+
+    #[test]
+    fn test_corrupt_envelope() {
+        let mb = BuilderImpl::default();
+        let res = mb.read_protocol_message(&[0xff, 0xff]);
+        assert!(matches!(res, Err(ProtocolError::EnvelopeDecode(_))));
+    }
+
+    #[test]
+    fn test_unknown_schema() {
+        let mb = BuilderImpl::default();
+        let mut envelope = mb.get_record(MESSAGE_ENVELOPE_SCHEMA).unwrap();
+        envelope.put("schema", Value::Bytes("insight.Unknown.avsc".into()));
+        envelope.put("payload", Value::Bytes(vec![]));
+        let bytes =
+            to_avro_datum(mb.get_schema(MESSAGE_ENVELOPE_SCHEMA).unwrap(), envelope).unwrap();
+        let res = mb.read_protocol_message(&bytes);
+        assert_eq!(
+            res,
+            Err(ProtocolError::UnknownSchema(String::from(
+                "insight.Unknown.avsc"
+            )))
+        );
+    }
+
+    #[test]
+    fn test_register_schema() {
+        let mut mb = BuilderImpl::default();
+        let name = mb
+            .register_schema(
+                r#"{"type": "record", "name": "Detection", "namespace": "insight.custom",
+                    "fields": [{"name": "unit", "type": "insight.storage.Unit"}]}"#,
+            )
+            .unwrap();
+        assert_eq!(name, "insight.custom.Detection");
+        assert!(mb.get_schema(&name).is_some());
+        assert!(mb.get_schema(UNIT_ELEMENT_MESSAGE_SCHEMA).is_some());
+
+        let res = mb.register_schema(r#"{"type": "array", "items": "long"}"#);
+        assert!(matches!(res, Err(ProtocolError::SchemaLoad(_))));
+    }
+
+    #[test]
+    fn test_schema_names() {
+        let mut mb = BuilderImpl::default();
+        let digest = mb
+            .register_schema(
+                r#"{"type": "fixed", "name": "Digest", "namespace": "insight.custom", "size": 4,
+                    "aliases": ["Hash", "insight.legacy.Digest"]}"#,
+            )
+            .unwrap();
+        assert_eq!(digest, "insight.custom.Digest");
+        for name in [
+            "insight.custom.Hash",
+            "insight.legacy.Digest",
+            "insight.custom.Digest.avsc",
+        ] {
+            assert_eq!(mb.resolve_name(name), Some("insight.custom.Digest"));
+        }
+        assert_eq!(mb.resolve_name("Digest"), None);
+        assert_eq!(
+            mb.resolve_name("insight.storage.Unit.avsc"),
+            Some(UNIT_SCHEMA)
+        );
+
+        let info = mb.schema_info("insight.custom.Hash").unwrap();
+        assert_eq!(info.name, digest);
+        assert_eq!(info.kind, "fixed");
+        assert_eq!(info.size, Some(4));
+        assert_eq!(
+            info.aliases,
+            ["insight.custom.Hash", "insight.legacy.Digest"]
+        );
+
+        // The envelopes of the earlier releases name the schema after its file.
+        let mut envelope = mb.get_record(MESSAGE_ENVELOPE_SCHEMA).unwrap();
+        let payload = Value::Fixed(4, vec![1, 2, 3, 4]);
+        let datum = to_avro_datum(mb.get_schema(&digest).unwrap(), payload.clone()).unwrap();
+        envelope.put("schema", Value::Bytes("insight.custom.Digest.avsc".into()));
+        envelope.put("payload", Value::Bytes(datum));
+        let bytes =
+            to_avro_datum(mb.get_schema(MESSAGE_ENVELOPE_SCHEMA).unwrap(), envelope).unwrap();
+        assert_eq!(
+            mb.read_protocol_message(&bytes),
+            Ok((digest.clone(), payload.clone()))
+        );
+
+        let bytes = mb
+            .pack_message_into_envelope(
+                "insight.legacy.Digest",
+                payload.clone(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(mb.read_protocol_message(&bytes), Ok((digest, payload)));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_schema_file_constants() {
+        let mb = BuilderImpl::default();
+        assert_eq!(
+            mb.resolve_name(crate::avro::UNIT_SCHEMA_FILE),
+            Some(UNIT_SCHEMA)
+        );
+        for (_, schema_name) in BuilderImpl::schema_files() {
+            assert!(mb
+                .resolve_name(&BuilderImpl::file_name(schema_name))
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_legacy_envelope_names() {
+        let mb = BuilderImpl::default();
+        let module = Value::Record(vec![("module_id".into(), Value::String("m".into()))]);
+        let bytes = mb
+            .pack_message_into_envelope(
+                KEEPALIVE_MESSAGE_SCHEMA,
+                module.clone(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+
+        // The earlier releases key the catalog by file name and read the envelope as is.
+        let by_file_name = mb
+            .directory
+            .iter()
+            .map(|(name, schema)| (BuilderImpl::file_name(name), schema))
+            .collect::<HashMap<_, _>>();
+        let envelope_schema = by_file_name["insight.transport.MessageEnvelope.avsc"];
+        let envelope = from_avro_datum(envelope_schema, &mut &bytes[..], None).unwrap();
+        let fields = RecordFields::new(MESSAGE_ENVELOPE_SCHEMA, &envelope).unwrap();
+        let schema_name = String::from_utf8(fields.get::<Vec<u8>>("schema").unwrap()).unwrap();
+        assert_eq!(schema_name, "insight.transport.KeepAliveMessage.avsc");
+        let payload = fields.get::<Vec<u8>>("payload").unwrap();
+        assert_eq!(
+            from_avro_datum(by_file_name[&schema_name], &mut &payload[..], None).unwrap(),
+            module
+        );
+
+        let mut full_name = BuilderImpl::default();
+        full_name.set_envelope_format(EnvelopeFormat::FullName);
+        let bytes = full_name
+            .pack_message_into_envelope(
+                KEEPALIVE_MESSAGE_SCHEMA,
+                module.clone(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+        let envelope = from_avro_datum(envelope_schema, &mut &bytes[..], None).unwrap();
+        let fields = RecordFields::new(MESSAGE_ENVELOPE_SCHEMA, &envelope).unwrap();
+        assert_eq!(
+            fields.get::<Vec<u8>>("schema").unwrap(),
+            KEEPALIVE_MESSAGE_SCHEMA.as_bytes()
+        );
+        assert_eq!(
+            mb.read_protocol_message(&bytes),
+            Ok((String::from(KEEPALIVE_MESSAGE_SCHEMA), module))
+        );
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_register_aliased_class() {
+        use crate::avro::Builder;
+        use pyo3::prelude::*;
+
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let mut builder = Builder::new(None).unwrap();
+            builder
+                .register_schema(
+                    r#"{"type": "record", "name": "Reading", "namespace": "insight.custom",
+                        "aliases": ["insight.legacy.Reading"],
+                        "fields": [{"name": "value", "type": "long"}]}"#,
+                )
+                .unwrap();
+            let module = PyModule::from_code(
+                py,
+                r#"
+class Reading:
+    SCHEMA = "insight.legacy.Reading"
+
+    def __init__(self, value):
+        self.value = value
+
+    def to_avro(self):
+        return {"value": self.value}
+
+    @classmethod
+    def from_avro(cls, record):
+        return cls(record["value"])
+"#,
+                "reading.py",
+                "reading",
+            )
+            .unwrap();
+            let cls = module.getattr("Reading").unwrap();
+            assert_eq!(
+                builder.register_class(cls).unwrap(),
+                "insight.custom.Reading"
+            );
+
+            let bytes = builder
+                .save(py, cls.call1((7,)).unwrap(), None, None)
+                .unwrap();
+            let loaded = builder.load(py, bytes).unwrap();
+            let loaded = loaded.as_ref(py);
+            assert!(loaded.is_instance(cls.downcast().unwrap()).unwrap());
+            assert_eq!(
+                loaded.getattr("value").unwrap().extract::<i64>().unwrap(),
+                7
+            );
+        });
+    }
+
+    const DETECTION_V1: &str = r#"{"type": "record", "name": "Detection",
+        "namespace": "insight.custom", "fields": [{"name": "id", "type": "long"}]}"#;
+    const DETECTION_V2: &str = r#"{"type": "record", "name": "Detection",
+        "namespace": "insight.custom", "fields": [{"name": "id", "type": "long"},
+        {"name": "label", "type": "string", "default": "none"}]}"#;
+
+    #[test]
+    fn test_schema_versions() {
+        let mut old = BuilderImpl::default();
+        let name = old.register_schema(DETECTION_V1).unwrap();
+        old.set_envelope_format(EnvelopeFormat::Versioned);
+        let bytes = old
+            .pack_message_into_envelope(
+                &name,
+                Value::Record(vec![("id".into(), Value::Long(1))]),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+
+        let mut new = BuilderImpl::default();
+        new.register_schema(DETECTION_V2).unwrap();
+        assert_eq!(
+            new.read_protocol_message(&bytes),
+            Err(ProtocolError::UnknownSchemaVersion {
+                schema: name.clone(),
+                fingerprint: old.fingerprint(&name).unwrap()
+            })
+        );
+
+        let fingerprint = new.register_schema_version(DETECTION_V1).unwrap();
+        assert_eq!(fingerprint, old.fingerprint(&name).unwrap());
+        assert_ne!(fingerprint, new.fingerprint(&name).unwrap());
+        let (schema, value) = new.read_protocol_message(&bytes).unwrap();
+        assert_eq!(schema, name);
+        assert_eq!(
+            value,
+            Value::Record(vec![
+                ("id".into(), Value::Long(1)),
+                ("label".into(), Value::String("none".into()))
+            ])
+        );
+    }
+
+    #[test]
+    fn test_replace_schema_version() {
+        let mut mb = BuilderImpl::default();
+        let name = mb.register_schema(DETECTION_V1).unwrap();
+        let v1 = mb.fingerprint(&name).unwrap();
+        assert_eq!(mb.register_schema(DETECTION_V2).unwrap(), name);
+        let v2 = mb.fingerprint(&name).unwrap();
+        assert_ne!(v1, v2);
+        assert!(mb.get_schema_version(&name, v1).is_ok());
+        assert!(mb.get_schema_version(&name, v2).is_ok());
+
+        let res = mb.register_schema_version(
+            r#"{"type": "record", "name": "Other", "fields": [{"name": "id", "type": "long"}]}"#,
+        );
+        assert!(matches!(res, Err(ProtocolError::UnknownSchema(_))));
+    }
+
+    #[test]
+    fn test_compact_envelope() {
+        let mut mb = BuilderImpl::default();
+        let keep_alive = || Value::Record(vec![("module_id".into(), Value::String("m".into()))]);
+        let named = mb
+            .pack_message_into_envelope(
+                KEEPALIVE_MESSAGE_SCHEMA,
+                keep_alive(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+        mb.set_envelope_format(EnvelopeFormat::Compact);
+        let compact = mb
+            .pack_message_into_envelope(
+                KEEPALIVE_MESSAGE_SCHEMA,
+                keep_alive(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            compact.len() + BuilderImpl::file_name(KEEPALIVE_MESSAGE_SCHEMA).len() - 10,
+            named.len()
+        );
+
+        for bytes in [&named, &compact] {
+            assert_eq!(
+                mb.read_protocol_message(bytes),
+                Ok((String::from(KEEPALIVE_MESSAGE_SCHEMA), keep_alive()))
+            );
+        }
+
+        let mut unknown = compact.clone();
+        unknown[3] ^= 0xff;
+        assert!(matches!(
+            mb.read_protocol_message(&unknown),
+            Err(ProtocolError::UnknownFingerprint(_))
+        ));
+    }
+
+    #[test]
+    fn test_single_object_encoding() {
+        let mut mb = BuilderImpl::default();
+        mb.set_envelope_format(EnvelopeFormat::SingleObject);
+        let keep_alive = Value::Record(vec![("module_id".into(), Value::String("m".into()))]);
+        let bytes = mb
+            .pack_message_into_envelope(
+                KEEPALIVE_MESSAGE_SCHEMA,
+                keep_alive.clone(),
+                &CompressionPolicy::default(),
+                None,
+            )
+            .unwrap();
+
+        let fingerprint = mb.fingerprint(KEEPALIVE_MESSAGE_SCHEMA).unwrap();
+        assert_eq!(bytes[..2], [0xC3, 0x01]);
+        assert_eq!(bytes[2..10], fingerprint.to_le_bytes());
+        let datum = to_avro_datum(
+            mb.get_schema(KEEPALIVE_MESSAGE_SCHEMA).unwrap(),
+            keep_alive.clone(),
+        )
+        .unwrap();
+        assert_eq!(bytes[10..], datum);
+
+        assert_eq!(
+            mb.read_protocol_message(&bytes),
+            Ok((String::from(KEEPALIVE_MESSAGE_SCHEMA), keep_alive))
+        );
     }
 }
@@ -0,0 +1,623 @@
+use crate::avro::{BuilderImpl, ProtocolMessage};
+use crate::compression::CompressionPolicy;
+use crate::discovery::SchemaScan;
+use crate::encryption::EncryptionKeys;
+use crate::envelope::EnvelopeFormat;
+use crate::error::Result;
+use crate::headers::Headers;
+use crate::limits::DecodeLimits;
+use crate::objects::{AnyMessage, CustomMessage, FromProtocolMessage, ToProtocolMessage};
+use crate::registry::{MessageHandler, Registry};
+use crate::signing::{KeyRing, SigningKey, VerifyPolicy};
+
+/// Encodes and decodes whole protocol messages without touching Python.
+///
+/// Every encode and decode takes `&self`, so one codec is shared between threads behind an `Arc`.
+pub struct Codec {
+    builder: BuilderImpl,
+    registry: Registry,
+}
+
+impl Codec {
+    /// Loads the schemas from `path_prefix` instead of the embedded ones.
+    pub fn new(path_prefix: &str) -> Result<Codec> {
+        Ok(Codec::from(BuilderImpl::new(path_prefix)?))
+    }
+
+    /// Loads the schemas found under `root`, see `BuilderImpl::discover`.
+    pub fn discover(root: &str) -> Result<(Codec, SchemaScan)> {
+        let (builder, scan) = BuilderImpl::discover(root)?;
+        Ok((Codec::from(builder), scan))
+    }
+
+    pub fn builder(&self) -> &BuilderImpl {
+        &self.builder
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut Registry {
+        &mut self.registry
+    }
+
+    /// Adds a schema to the catalog, see `BuilderImpl::register_schema`.
+    pub fn register_schema(&mut self, json: &str) -> Result<String> {
+        self.builder.register_schema(json)
+    }
+
+    /// Adds another version of a known schema, see `BuilderImpl::register_schema_version`.
+    pub fn register_schema_version(&mut self, json: &str) -> Result<u64> {
+        self.builder.register_schema_version(json)
+    }
+
+    pub fn set_envelope_format(&mut self, format: EnvelopeFormat) {
+        self.builder.set_envelope_format(format)
+    }
+
+    pub fn set_compression(&mut self, policy: CompressionPolicy) {
+        self.builder.set_compression(policy)
+    }
+
+    pub fn set_checksum(&mut self, checksum: bool) {
+        self.builder.set_checksum(checksum)
+    }
+
+    pub fn set_decode_limits(&mut self, limits: DecodeLimits) {
+        self.builder.set_decode_limits(limits)
+    }
+
+    pub fn set_signing_key(&mut self, key: Option<SigningKey>) {
+        self.builder.set_signing_key(key)
+    }
+
+    pub fn key_ring_mut(&mut self) -> &mut KeyRing {
+        self.builder.key_ring_mut()
+    }
+
+    pub fn set_verify_policy(&mut self, policy: VerifyPolicy) {
+        self.builder.set_verify_policy(policy)
+    }
+
+    pub fn encryption_keys_mut(&mut self) -> &mut EncryptionKeys {
+        self.builder.encryption_keys_mut()
+    }
+
+    /// Decodes messages of the known schema `schema` into the custom type `T`.
+    pub fn register_type<T>(&mut self, schema: &str) -> Result<()>
+    where
+        T: FromProtocolMessage + CustomMessage + 'static,
+    {
+        let schema = self.builder.known_name(schema)?;
+        self.registry
+            .register(schema, MessageHandler::custom::<T>());
+        Ok(())
+    }
+
+    pub fn encode<T: ToProtocolMessage>(&self, message: &T) -> Result<Vec<u8>> {
+        self.encode_message(message.save(&self.builder)?)
+    }
+
+    pub fn encode_with_headers<T: ToProtocolMessage>(
+        &self,
+        message: &T,
+        headers: Headers,
+    ) -> Result<Vec<u8>> {
+        let mut message = message.save(&self.builder)?;
+        message.headers = headers;
+        self.encode_message(message)
+    }
+
+    /// Seals the payload with the key `key_id` whatever the key its schema is sealed with.
+    pub fn encode_sealed<T: ToProtocolMessage>(
+        &self,
+        message: &T,
+        key_id: &str,
+    ) -> Result<Vec<u8>> {
+        self.encode_sealed_message(message.save(&self.builder)?, key_id)
+    }
+
+    pub fn decode(&self, from: &[u8]) -> Result<AnyMessage> {
+        self.registry.decode(&self.decode_message(from)?)
+    }
+
+    pub fn decode_with_headers(&self, from: &[u8]) -> Result<(AnyMessage, Headers)> {
+        let message = self.decode_message(from)?;
+        Ok((self.registry.decode(&message)?, message.headers))
+    }
+
+    /// Packs the messages into one `MessageBatch` frame.
+    pub fn encode_batch<T: ToProtocolMessage>(&self, messages: &[T]) -> Result<Vec<u8>> {
+        self.encode_messages(
+            messages
+                .iter()
+                .map(|m| m.save(&self.builder))
+                .collect::<Result<_>>()?,
+        )
+    }
+
+    pub fn decode_batch(&self, from: &[u8]) -> Result<Vec<AnyMessage>> {
+        self.decode_messages(from)?
+            .iter()
+            .map(|m| self.registry.decode(m))
+            .collect()
+    }
+
+    pub fn encode_messages(&self, messages: Vec<ProtocolMessage>) -> Result<Vec<u8>> {
+        self.builder.pack_batch(messages)
+    }
+
+    pub fn decode_messages(&self, from: &[u8]) -> Result<Vec<ProtocolMessage>> {
+        self.builder.read_batch(from)
+    }
+
+    /// Decodes the JSON document of `BuilderImpl::message_to_json`.
+    pub fn decode_json(&self, json: &str) -> Result<AnyMessage> {
+        self.registry.decode(&self.builder.message_from_json(json)?)
+    }
+
+    pub fn encode_message(&self, message: ProtocolMessage) -> Result<Vec<u8>> {
+        self.builder.pack_message(message)
+    }
+
+    pub fn encode_sealed_message(&self, message: ProtocolMessage, key_id: &str) -> Result<Vec<u8>> {
+        self.builder.pack_sealed_message(message, Some(key_id))
+    }
+
+    pub fn decode_message(&self, from: &[u8]) -> Result<ProtocolMessage> {
+        self.builder.read_message(from)
+    }
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::from(BuilderImpl::default())
+    }
+}
+
+impl From<BuilderImpl> for Codec {
+    fn from(builder: BuilderImpl) -> Self {
+        Codec {
+            builder,
+            registry: Registry::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::{
+        BuilderImpl, ProtocolMessage, KEEPALIVE_MESSAGE_SCHEMA, PING_REQUEST_RESPONSE_SCHEMA,
+    };
+    use crate::codec::Codec;
+    use crate::compression::{Compression, CompressionPolicy};
+    use crate::envelope::{EnvelopeFormat, SEALED_MAGIC};
+    use crate::error::{ProtocolError, Result};
+    use crate::headers::Headers;
+    use crate::limits::DecodeLimits;
+    use crate::objects::services::keep_alive::KeepAliveMessage;
+    use crate::objects::services::ping::{PingRequestResponse, PingRequestResponseType};
+    use crate::objects::{AnyMessage, FromProtocolMessage, ToProtocolMessage};
+    use crate::primitives::{get_track_type_enum, TrackType};
+    use crate::record::RecordFields;
+    use crate::signing::{SignatureStatus, SigningKey, VerifyPolicy};
+    use avro_rs::types::Value;
+    use std::sync::Arc;
+    use std::thread;
+
+    const DETECTION_SCHEMA: &str = "insight.custom.Detection";
+    const DETECTION_SCHEMA_JSON: &str = r#"{
+        "type": "record",
+        "name": "Detection",
+        "namespace": "insight.custom",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "track_type", "type": "insight.storage.TrackType"}
+        ]
+    }"#;
+
+    #[derive(Debug, PartialEq)]
+    struct Detection {
+        id: i64,
+    }
+
+    impl ToProtocolMessage for Detection {
+        fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+            let mut obj = mb.get_record(DETECTION_SCHEMA)?;
+            obj.put("id", Value::Long(self.id));
+            obj.put("track_type", get_track_type_enum(&TrackType::Video)?);
+            Ok(ProtocolMessage::new(DETECTION_SCHEMA, Value::from(obj)))
+        }
+    }
+
+    impl FromProtocolMessage for Detection {
+        fn load(message: &ProtocolMessage) -> Result<Self> {
+            let fields = RecordFields::from_message(message)?;
+            Ok(Detection {
+                id: fields.get("id")?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_custom_type() {
+        let mut codec = Codec::default();
+        let schema = codec.register_schema(DETECTION_SCHEMA_JSON).unwrap();
+        assert_eq!(schema, DETECTION_SCHEMA);
+        codec.register_type::<Detection>(&schema).unwrap();
+
+        let detection = Detection { id: 7 };
+        let decoded = codec.decode(&codec.encode(&detection).unwrap()).unwrap();
+        assert_eq!(decoded.schema(), DETECTION_SCHEMA);
+        match decoded {
+            AnyMessage::Custom { message, .. } => {
+                assert_eq!(message.as_any().downcast_ref(), Some(&detection))
+            }
+            _ => panic!("Custom message expected"),
+        }
+    }
+
+    #[test]
+    fn test_custom_type_unknown_schema() {
+        let mut codec = Codec::default();
+        assert_eq!(
+            codec.register_type::<Detection>(DETECTION_SCHEMA),
+            Err(ProtocolError::UnknownSchema(String::from(DETECTION_SCHEMA)))
+        );
+    }
+
+    #[test]
+    fn test_decode_dispatch() {
+        let codec = Codec::default();
+        let ping =
+            PingRequestResponse::new(1, String::from("test"), PingRequestResponseType::Request);
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+
+        let decoded = codec.decode(&codec.encode(&ping).unwrap()).unwrap();
+        assert_eq!(decoded.schema(), PING_REQUEST_RESPONSE_SCHEMA);
+        assert_eq!(decoded, AnyMessage::from(ping));
+
+        let decoded = codec.decode(&codec.encode(&keep_alive).unwrap()).unwrap();
+        assert_eq!(decoded.schema(), KEEPALIVE_MESSAGE_SCHEMA);
+        assert_eq!(decoded, AnyMessage::from(keep_alive));
+    }
+
+    #[test]
+    fn test_decode_wrong_type() {
+        let codec = Codec::default();
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let message = codec
+            .decode_message(&codec.encode(&keep_alive).unwrap())
+            .unwrap();
+        assert!(matches!(
+            PingRequestResponse::load(&message),
+            Err(ProtocolError::SchemaMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_headers() {
+        let mut codec = Codec::default();
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let headers = Headers {
+            module_id: Some(String::from("module")),
+            correlation_id: Some(String::from("42")),
+            extra: [(String::from("key"), String::from("value"))].into(),
+            ..Headers::stamped()
+        };
+
+        for format in [EnvelopeFormat::Named, EnvelopeFormat::Compact] {
+            codec.set_envelope_format(format);
+            let bytes = codec
+                .encode_with_headers(&keep_alive, headers.clone())
+                .unwrap();
+            assert_eq!(
+                codec.decode_with_headers(&bytes).unwrap(),
+                (AnyMessage::from(keep_alive.clone()), headers.clone())
+            );
+            assert_eq!(
+                codec.decode(&bytes).unwrap(),
+                AnyMessage::from(keep_alive.clone())
+            );
+        }
+
+        let bytes = codec.encode(&keep_alive).unwrap();
+        assert_eq!(
+            codec.decode_with_headers(&bytes).unwrap(),
+            (AnyMessage::from(keep_alive.clone()), Headers::default())
+        );
+
+        codec.set_envelope_format(EnvelopeFormat::SingleObject);
+        assert!(matches!(
+            codec.encode_with_headers(&keep_alive, headers),
+            Err(ProtocolError::Encode(_))
+        ));
+    }
+
+    #[test]
+    fn test_compression() {
+        let mut codec = Codec::default();
+        let ping = PingRequestResponse::new(1, "x".repeat(1000), PingRequestResponseType::Request);
+        let plain = codec.encode(&ping).unwrap();
+
+        codec.set_compression(CompressionPolicy::new(Compression::Snappy, 2000));
+        assert_eq!(codec.encode(&ping).unwrap(), plain);
+
+        codec.set_compression(CompressionPolicy::new(Compression::Snappy, 100));
+        let compressed = codec
+            .encode_with_headers(&ping, Headers::stamped())
+            .unwrap();
+        assert!(compressed.len() < plain.len());
+        assert_eq!(
+            Codec::default().decode(&compressed).unwrap(),
+            AnyMessage::from(ping)
+        );
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut codec = Codec::default();
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let plain = codec.encode(&keep_alive).unwrap();
+
+        codec.set_checksum(true);
+        for format in [EnvelopeFormat::Named, EnvelopeFormat::Compact] {
+            codec.set_envelope_format(format);
+            let mut bytes = codec
+                .encode_with_headers(&keep_alive, Headers::stamped())
+                .unwrap();
+            assert_eq!(
+                Codec::default().decode(&bytes).unwrap(),
+                AnyMessage::from(keep_alive.clone())
+            );
+
+            let last = bytes.len() - 1;
+            bytes[last] ^= 0x01;
+            assert!(matches!(
+                codec.decode(&bytes),
+                Err(ProtocolError::ChecksumMismatch { .. })
+            ));
+        }
+        assert_eq!(codec.decode(&plain).unwrap(), AnyMessage::from(keep_alive));
+    }
+
+    #[test]
+    fn test_signing() {
+        let mut sender = Codec::default();
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let unsigned = sender.encode(&keep_alive).unwrap();
+        sender.set_signing_key(Some(SigningKey::new("edge-1", b"secret").unwrap()));
+        let mut signed = sender
+            .encode_with_headers(&keep_alive, Headers::stamped())
+            .unwrap();
+        let batch = sender
+            .encode_batch(std::slice::from_ref(&keep_alive))
+            .unwrap();
+
+        let mut receiver = Codec::default();
+        let message = receiver.decode_message(&signed).unwrap();
+        assert_eq!(message.signature.status, SignatureStatus::UnknownKey);
+        assert_eq!(message.signature.key_id.as_deref(), Some("edge-1"));
+
+        receiver.key_ring_mut().insert("edge-1", b"secret").unwrap();
+        receiver.set_verify_policy(VerifyPolicy::Require);
+        assert_eq!(
+            receiver.decode(&signed).unwrap(),
+            AnyMessage::from(keep_alive.clone())
+        );
+        assert!(receiver.decode_messages(&batch).unwrap()[0]
+            .signature
+            .is_valid());
+        assert!(matches!(
+            receiver.decode(&unsigned),
+            Err(ProtocolError::Signature(_))
+        ));
+
+        let last = signed.len() - 1;
+        signed[last] ^= 0x01;
+        assert!(matches!(
+            receiver.decode(&signed),
+            Err(ProtocolError::Signature(_))
+        ));
+        receiver.set_verify_policy(VerifyPolicy::Flag);
+        assert_eq!(
+            receiver.builder().verify(&signed).unwrap().status,
+            SignatureStatus::Invalid
+        );
+    }
+
+    #[test]
+    fn test_encryption() {
+        let mut sender = Codec::default();
+        sender
+            .encryption_keys_mut()
+            .insert("video-1", &[7; 32])
+            .unwrap();
+        sender
+            .encryption_keys_mut()
+            .insert("video-2", &[8; 32])
+            .unwrap();
+        sender
+            .encryption_keys_mut()
+            .set_current(Some("video-1"))
+            .unwrap();
+        let module = "m".repeat(40);
+        let keep_alive = KeepAliveMessage::new(module.clone());
+
+        let mut receiver = Codec::default();
+        receiver
+            .encryption_keys_mut()
+            .insert("video-1", &[7; 32])
+            .unwrap();
+        for format in [EnvelopeFormat::Named, EnvelopeFormat::Compact] {
+            sender.set_envelope_format(format);
+            let bytes = sender.encode(&keep_alive).unwrap();
+            assert!(!bytes.windows(module.len()).any(|w| w == module.as_bytes()));
+            assert_eq!(
+                receiver.decode(&bytes).unwrap(),
+                AnyMessage::from(keep_alive.clone())
+            );
+        }
+
+        sender.set_envelope_format(EnvelopeFormat::Named);
+        let bytes = sender.encode(&keep_alive).unwrap();
+        let schema = KEEPALIVE_MESSAGE_SCHEMA.as_bytes();
+        assert!(bytes.windows(schema.len()).any(|w| w == schema));
+
+        let rotated = sender.encode_sealed(&keep_alive, "video-2").unwrap();
+        assert_eq!(
+            receiver.decode(&rotated),
+            Err(ProtocolError::UnknownEncryptionKey(String::from("video-2")))
+        );
+        assert!(matches!(
+            Codec::default().decode(&bytes),
+            Err(ProtocolError::UnknownEncryptionKey(_))
+        ));
+
+        let mut forged = bytes.clone();
+        let last = forged.len() - 1;
+        forged[last] ^= 0x01;
+        assert!(matches!(
+            receiver.decode(&forged),
+            Err(ProtocolError::Encryption(_))
+        ));
+
+        // The versioned fingerprint following the seal is authenticated too.
+        sender.set_envelope_format(EnvelopeFormat::Versioned);
+        let mut forged = sender.encode(&keep_alive).unwrap();
+        let fingerprint = SEALED_MAGIC.len() + 1 + "video-1".len() + 12 + 2;
+        forged[fingerprint] ^= 0x01;
+        assert!(matches!(
+            receiver.decode(&forged),
+            Err(ProtocolError::Encryption(_))
+        ));
+    }
+
+    #[test]
+    fn test_sealed_compression() {
+        let mut codec = Codec::default();
+        codec
+            .encryption_keys_mut()
+            .insert("video-1", &[7; 32])
+            .unwrap();
+        codec
+            .encryption_keys_mut()
+            .set_current(Some("video-1"))
+            .unwrap();
+        let ping = PingRequestResponse::new(1, "x".repeat(1000), PingRequestResponseType::Request);
+        let sealed = codec.encode(&ping).unwrap();
+
+        codec.set_compression(CompressionPolicy::new(Compression::Snappy, 100));
+        let compressed = codec.encode(&ping).unwrap();
+        assert!(compressed.len() < sealed.len() / 2);
+        assert_eq!(
+            codec.decode(&compressed).unwrap(),
+            AnyMessage::from(ping.clone())
+        );
+
+        // The items of a batch are not compressed one by one, sealed or not.
+        let batch = codec.encode_batch(std::slice::from_ref(&ping)).unwrap();
+        assert!(batch.len() > sealed.len() / 2);
+        assert_eq!(
+            codec.decode_batch(&batch).unwrap(),
+            vec![AnyMessage::from(ping)]
+        );
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BuilderImpl>();
+        assert_send_sync::<Codec>();
+
+        let codec = Arc::new(Codec::default());
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let bytes = codec.encode(&keep_alive).unwrap();
+        let workers = (0..4)
+            .map(|_| {
+                let codec = Arc::clone(&codec);
+                let bytes = bytes.clone();
+                thread::spawn(move || codec.decode(&bytes))
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            assert_eq!(
+                worker.join().unwrap().unwrap(),
+                AnyMessage::from(keep_alive.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let mut codec = Codec::default();
+        let ping = PingRequestResponse::new(1, "x".repeat(1000), PingRequestResponseType::Request);
+        let bytes = codec.encode(&ping).unwrap();
+
+        codec.set_decode_limits(DecodeLimits {
+            max_bytes_length: 100,
+            ..DecodeLimits::default()
+        });
+        assert!(matches!(
+            codec.decode(&bytes),
+            Err(ProtocolError::LimitExceeded { value, .. }) if value > 1000
+        ));
+
+        codec.set_decode_limits(DecodeLimits {
+            max_message_size: 100,
+            ..DecodeLimits::default()
+        });
+        assert!(matches!(
+            codec.decode(&bytes),
+            Err(ProtocolError::LimitExceeded { .. })
+        ));
+
+        codec.set_decode_limits(DecodeLimits {
+            max_depth: 0,
+            ..DecodeLimits::default()
+        });
+        assert!(matches!(
+            codec.decode(&bytes),
+            Err(ProtocolError::LimitExceeded { .. })
+        ));
+
+        codec.set_decode_limits(DecodeLimits::default());
+        assert_eq!(codec.decode(&bytes).unwrap(), AnyMessage::from(ping));
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut codec = Codec::default();
+        let ping = PingRequestResponse::new(1, "x".repeat(1000), PingRequestResponseType::Request);
+        let keep_alive = KeepAliveMessage::new(String::from("module"));
+        let messages = vec![
+            AnyMessage::from(ping.clone()),
+            AnyMessage::from(keep_alive.clone()),
+        ];
+        let plain = codec.encode_batch(&messages).unwrap();
+        assert_eq!(codec.decode_batch(&plain).unwrap(), messages);
+        assert_eq!(
+            codec
+                .decode_batch(&codec.encode_messages(vec![]).unwrap())
+                .unwrap(),
+            []
+        );
+
+        let single = codec.encode(&keep_alive).unwrap();
+        assert_eq!(
+            codec.decode_batch(&single).unwrap(),
+            [AnyMessage::from(keep_alive)]
+        );
+
+        let mut message = ping.save(codec.builder()).unwrap();
+        message.headers = Headers::stamped();
+        codec.set_compression(CompressionPolicy::new(Compression::Deflate, 100));
+        let compressed = codec.encode_messages(vec![message.clone()]).unwrap();
+        assert!(compressed.len() < plain.len());
+        assert_eq!(
+            Codec::default().decode_messages(&compressed).unwrap(),
+            [message]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::error::{ProtocolError, Result};
+use crate::limits::DecodeLimits;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::io::{self, Read, Write};
+
+/// Codec the framed message is compressed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub enum Compression {
+    #[default]
+    None,
+    Deflate,
+    Snappy,
+    /// Requires the `zstd` cargo feature.
+    Zstd,
+}
+
+impl Compression {
+    /// Codec flag stored in the compressed frame.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+            Compression::Snappy => 2,
+            Compression::Zstd => 3,
+        }
+    }
+
+    pub(crate) fn from_id(id: u8) -> Result<Compression> {
+        match id {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            2 => Ok(Compression::Snappy),
+            3 => Ok(Compression::Zstd),
+            _ => Err(ProtocolError::EnvelopeDecode(format!(
+                "Unknown compression codec ({})",
+                id
+            ))),
+        }
+    }
+
+    pub(crate) fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        let encode_error = |e: String| ProtocolError::Encode(format!("{} ({:?})", e, self));
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(
+                    Vec::with_capacity(data.len()),
+                    flate2::Compression::default(),
+                );
+                encoder
+                    .write_all(data)
+                    .and_then(|()| encoder.finish())
+                    .map_err(|e| encode_error(e.to_string()))
+            }
+            Compression::Snappy => {
+                avro_compress(avro_rs::Codec::Snappy, data).map_err(|e| encode_error(e.to_string()))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                zstd::bulk::compress(data, 0).map_err(|e| encode_error(e.to_string()))
+            }
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(encode_error(String::from(
+                "The library is built without zstd support",
+            ))),
+        }
+    }
+
+    /// Decompresses the data, failing once the output exceeds the message size limit.
+    ///
+    /// The output is bounded before it is allocated: the snappy length is checked up front,
+    /// deflate and zstd are inflated by a stream reader stopping past the limit.
+    pub(crate) fn decompress(self, data: &[u8], limits: &DecodeLimits) -> Result<Vec<u8>> {
+        let decode_error = |e: String| {
+            ProtocolError::EnvelopeDecode(format!("Failed to decompress ({:?}): {}", self, e))
+        };
+        let decompressed = match self {
+            Compression::None => data.to_vec(),
+            Compression::Deflate => read_bounded(flate2::read::DeflateDecoder::new(data), limits)
+                .map_err(|e| decode_error(e.to_string()))?,
+            Compression::Snappy => {
+                if let Some(len) = snappy_length(data) {
+                    limits.check_message_size(len)?;
+                }
+                avro_decompress(avro_rs::Codec::Snappy, data)
+                    .map_err(|e| decode_error(e.to_string()))?
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::read::Decoder::new(data)
+                .and_then(|decoder| read_bounded(decoder, limits))
+                .map_err(|e| decode_error(e.to_string()))?,
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => {
+                return Err(decode_error(String::from(
+                    "the library is built without zstd support",
+                )))
+            }
+        };
+        limits.check_message_size(decompressed.len())?;
+        Ok(decompressed)
+    }
+}
+
+/// Reads the decompressed stream up to one byte past the message size limit, so that the
+/// limit check fails on an oversized output without inflating it whole.
+fn read_bounded(reader: impl Read, limits: &DecodeLimits) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    reader
+        .take((limits.max_message_size as u64).saturating_add(1))
+        .read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+fn avro_compress(codec: avro_rs::Codec, data: &[u8]) -> avro_rs::AvroResult<Vec<u8>> {
+    let mut buf = data.to_vec();
+    codec.compress(&mut buf)?;
+    Ok(buf)
+}
+
+/// Uncompressed length stored ahead of a raw snappy block.
+fn snappy_length(data: &[u8]) -> Option<usize> {
+    let mut len: u64 = 0;
+    for (i, byte) in data.iter().take(5).enumerate() {
+        len |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return usize::try_from(len).ok();
+        }
+    }
+    None
+}
+
+fn avro_decompress(codec: avro_rs::Codec, data: &[u8]) -> avro_rs::AvroResult<Vec<u8>> {
+    let mut buf = data.to_vec();
+    codec.decompress(&mut buf)?;
+    Ok(buf)
+}
+
+/// Compresses the messages whose framed size reaches `threshold` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionPolicy {
+    pub compression: Compression,
+    pub threshold: usize,
+}
+
+impl CompressionPolicy {
+    pub fn new(compression: Compression, threshold: usize) -> Self {
+        CompressionPolicy {
+            compression,
+            threshold,
+        }
+    }
+
+    /// The compressed data, `None` if the policy leaves it as is or it does not shrink.
+    pub(crate) fn apply(&self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        if self.compression == Compression::None || data.len() < self.threshold {
+            return Ok(None);
+        }
+        let compressed = self.compression.compress(data)?;
+        Ok((compressed.len() < data.len()).then_some(compressed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{Compression, CompressionPolicy};
+    use crate::error::ProtocolError;
+    use crate::limits::DecodeLimits;
+
+    #[test]
+    fn test_roundtrip() {
+        let data = [7u8; 1000];
+        let mut codecs = vec![Compression::Deflate, Compression::Snappy];
+        if cfg!(feature = "zstd") {
+            codecs.push(Compression::Zstd);
+        }
+        for compression in codecs {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compressed.len() < data.len(), "{:?}", compression);
+            assert_eq!(
+                compression
+                    .decompress(&compressed, &DecodeLimits::default())
+                    .unwrap(),
+                data
+            );
+            assert!(matches!(
+                compression.decompress(&compressed, &DecodeLimits::new(999, 0, 0, 0)),
+                Err(ProtocolError::LimitExceeded { .. })
+            ));
+            assert_eq!(Compression::from_id(compression.id()), Ok(compression));
+        }
+        assert!(Compression::from_id(100).is_err());
+    }
+
+    #[test]
+    fn test_deflate_bomb() {
+        use flate2::write::DeflateEncoder;
+        use std::io::Write;
+
+        // 256 MiB of zeros deflate to a few hundred KiB.
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        let chunk = vec![0u8; 1 << 20];
+        for _ in 0..256 {
+            encoder.write_all(&chunk).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 1 << 20);
+        assert!(matches!(
+            Compression::Deflate.decompress(&bomb, &DecodeLimits::new(1 << 20, 0, 0, 0)),
+            Err(ProtocolError::LimitExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = CompressionPolicy::new(Compression::Deflate, 100);
+        assert_eq!(policy.apply(&[7u8; 99]).unwrap(), None);
+        assert!(policy.apply(&[7u8; 100]).unwrap().is_some());
+        assert_eq!(policy.apply(&[1, 2, 3]).unwrap(), None);
+        assert_eq!(
+            CompressionPolicy::default().apply(&[7u8; 1000]).unwrap(),
+            None
+        );
+    }
+}
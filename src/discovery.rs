@@ -0,0 +1,433 @@
+use crate::error::{ProtocolError, Result};
+use crate::schemas::EMBEDDED_SCHEMAS;
+use avro_rs::Schema;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PRIMITIVES: &[&str] = &[
+    "null", "boolean", "int", "long", "float", "double", "bytes", "string",
+];
+
+/// A schema file the scan could not load.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct SchemaLoadFailure {
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for SchemaLoadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.reason)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SchemaLoadFailure {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+/// Outcome of scanning a schema tree.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct SchemaScan {
+    /// Files loaded, in the order their dependencies require.
+    pub loaded: Vec<String>,
+    /// Built-in schemas the tree lacks, taken from the embedded ones.
+    pub embedded: Vec<String>,
+    pub failures: Vec<SchemaLoadFailure>,
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SchemaScan {
+    fn __repr__(&self) -> String {
+        format!(
+            "SchemaScan {{ loaded: {}, embedded: {}, failures: {} }}",
+            self.loaded.len(),
+            self.embedded.len(),
+            self.failures.len()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+/// A schema source with the named types it defines and the ones it refers to.
+#[derive(Debug)]
+struct Source {
+    path: String,
+    json: String,
+    embedded: bool,
+    defines: Vec<String>,
+    references: Vec<String>,
+}
+
+impl Source {
+    fn new(path: String, json: String, embedded: bool) -> std::result::Result<Source, String> {
+        let value: JsonValue =
+            serde_json::from_str(&json).map_err(|e| format!("Invalid JSON: {}", e))?;
+        let mut names = Names::default();
+        names.walk(&value, None);
+        if names.defines.is_empty() {
+            return Err(String::from(
+                "No named record, enum or fixed type is defined",
+            ));
+        }
+        let mut references = Vec::new();
+        for name in names.references {
+            if !names.defines.contains(&name) && !references.contains(&name) {
+                references.push(name);
+            }
+        }
+        Ok(Source {
+            path,
+            json,
+            embedded,
+            defines: names.defines,
+            references,
+        })
+    }
+}
+
+/// Full names of the named types a schema defines and refers to.
+#[derive(Debug, Default)]
+struct Names {
+    defines: Vec<String>,
+    references: Vec<String>,
+}
+
+impl Names {
+    fn walk(&mut self, value: &JsonValue, namespace: Option<&str>) {
+        match value {
+            JsonValue::String(name) if !PRIMITIVES.contains(&name.as_str()) => {
+                self.references.push(qualify(name, namespace));
+            }
+            JsonValue::Array(variants) => {
+                for variant in variants {
+                    self.walk(variant, namespace);
+                }
+            }
+            JsonValue::Object(object) => match object.get("type") {
+                Some(JsonValue::String(kind)) => match kind.as_str() {
+                    "record" | "error" | "enum" | "fixed" => self.define(object, namespace),
+                    "array" => self.walk_member(object, "items", namespace),
+                    "map" => self.walk_member(object, "values", namespace),
+                    _ => self.walk(&object["type"], namespace),
+                },
+                Some(kind) => self.walk(kind, namespace),
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn walk_member(&mut self, object: &Map<String, JsonValue>, key: &str, namespace: Option<&str>) {
+        if let Some(member) = object.get(key) {
+            self.walk(member, namespace);
+        }
+    }
+
+    fn define(&mut self, object: &Map<String, JsonValue>, namespace: Option<&str>) {
+        let name = match object.get("name").and_then(JsonValue::as_str) {
+            Some(name) => name,
+            None => return,
+        };
+        let namespace = object
+            .get("namespace")
+            .and_then(JsonValue::as_str)
+            .or(namespace);
+        let fullname = qualify(name, namespace);
+        // The types nested into the record inherit the namespace of its full name.
+        let namespace = fullname.rsplit_once('.').map(|(ns, _)| String::from(ns));
+        self.defines.push(fullname);
+        let fields = object.get("fields").and_then(JsonValue::as_array);
+        for field in fields.into_iter().flatten() {
+            if let Some(field_type) = field.get("type") {
+                self.walk(field_type, namespace.as_deref());
+            }
+        }
+    }
+}
+
+fn qualify(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(namespace) if !name.contains('.') && !namespace.is_empty() => {
+            format!("{}.{}", namespace, name)
+        }
+        _ => String::from(name),
+    }
+}
+
+/// Collects the `.avsc` files under `dir`. A symlinked directory is not followed, so a link
+/// cycle cannot make the scan recurse forever; a symlinked file is read.
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_files(&path, files)?;
+        } else if (file_type.is_file() || (file_type.is_symlink() && path.is_file()))
+            && path.extension().is_some_and(|e| e == "avsc")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Orders the sources so that every one follows the sources defining the types it refers to.
+/// A cycle is broken arbitrarily; its sources then fail on the unresolved reference.
+fn dependency_order(sources: &[Source]) -> Vec<usize> {
+    fn visit(
+        i: usize,
+        sources: &[Source],
+        by_name: &HashMap<&str, usize>,
+        visited: &mut [bool],
+        order: &mut Vec<usize>,
+    ) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+        for reference in &sources[i].references {
+            if let Some(&j) = by_name.get(reference.as_str()) {
+                visit(j, sources, by_name, visited, order);
+            }
+        }
+        order.push(i);
+    }
+
+    let mut by_name = HashMap::new();
+    for (i, source) in sources.iter().enumerate() {
+        for name in &source.defines {
+            by_name.entry(name.as_str()).or_insert(i);
+        }
+    }
+    let mut visited = vec![false; sources.len()];
+    let mut order = Vec::with_capacity(sources.len());
+    for i in 0..sources.len() {
+        visit(i, sources, &by_name, &mut visited, &mut order);
+    }
+    order
+}
+
+/// The position in `candidates` of the first source failing to parse along with the ones
+/// before it, and the reason it fails; `error` is the reason the whole list fails.
+///
+/// The candidates follow their dependencies, so every prefix of the list is parsed on its own
+/// and the first failing one is found by bisection.
+fn first_failure(sources: &[Source], candidates: &[usize], error: String) -> (usize, String) {
+    let parse = |len: usize| {
+        let jsons = candidates[..len]
+            .iter()
+            .map(|&i| sources[i].json.as_str())
+            .collect::<Vec<_>>();
+        Schema::parse_list(&jsons)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    };
+    let (mut parsed, mut failed, mut reason) = (0, candidates.len(), error);
+    while failed - parsed > 1 {
+        let middle = parsed + (failed - parsed) / 2;
+        match parse(middle) {
+            Ok(()) => parsed = middle,
+            Err(e) => (failed, reason) = (middle, e),
+        }
+    }
+    (failed - 1, reason)
+}
+
+/// Scans the tree under `root` for `.avsc` files and returns the sources which parse, in
+/// dependency order, with the report of the scan.
+///
+/// The redefined types and the unresolved references are found from the JSON alone, then the
+/// remaining sources are parsed together once. Should they fail, the failing source is found
+/// by bisection and dropped with the sources depending on it, so a broken file is reported
+/// without failing the rest. The built-in schemas the tree does not define are taken from the
+/// embedded ones.
+pub(crate) fn discover(root: &Path) -> Result<(Vec<String>, SchemaScan)> {
+    let mut files = Vec::new();
+    collect_files(root, &mut files).map_err(|e| {
+        ProtocolError::SchemaLoad(format!(
+            "Schema root {} cannot be scanned: {}",
+            root.display(),
+            e
+        ))
+    })?;
+    files.sort();
+
+    let mut scan = SchemaScan::default();
+    let mut sources = Vec::new();
+    for path in files {
+        let path = path.display().to_string();
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Source::new(path.clone(), json, false))
+        {
+            Ok(source) => sources.push(source),
+            Err(reason) => scan.failures.push(SchemaLoadFailure { path, reason }),
+        }
+    }
+    let defined = sources
+        .iter()
+        .flat_map(|s| s.defines.iter().cloned())
+        .collect::<HashSet<_>>();
+    for (_, name, json) in EMBEDDED_SCHEMAS {
+        if let Ok(source) = Source::new(String::from(*name), String::from(*json), true) {
+            if !defined.contains(&source.defines[0]) {
+                sources.push(source);
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    let mut available = HashSet::new();
+    for i in dependency_order(&sources) {
+        let source = &sources[i];
+        let redefined = source.defines.iter().find(|n| available.contains(*n));
+        let missing = source.references.iter().find(|n| !available.contains(*n));
+        let reason = match (redefined, missing) {
+            (Some(name), _) => format!("The type ({}) is already defined", name),
+            (None, Some(name)) => format!("Unresolved reference ({})", name),
+            (None, None) => {
+                candidates.push(i);
+                available.extend(source.defines.iter().cloned());
+                continue;
+            }
+        };
+        scan.failures.push(SchemaLoadFailure {
+            path: source.path.clone(),
+            reason,
+        });
+    }
+
+    loop {
+        let jsons = candidates
+            .iter()
+            .map(|&i| sources[i].json.as_str())
+            .collect::<Vec<_>>();
+        let error = match Schema::parse_list(&jsons) {
+            Ok(_) => break,
+            Err(e) => e.to_string(),
+        };
+        let (position, reason) = first_failure(&sources, &candidates, error);
+        let failed = candidates.remove(position);
+        scan.failures.push(SchemaLoadFailure {
+            path: sources[failed].path.clone(),
+            reason,
+        });
+        let mut dropped = sources[failed].defines.iter().collect::<HashSet<_>>();
+        candidates.retain(|&i| {
+            let source = &sources[i];
+            match source.references.iter().find(|n| dropped.contains(n)) {
+                Some(name) => {
+                    scan.failures.push(SchemaLoadFailure {
+                        path: source.path.clone(),
+                        reason: format!("Unresolved reference ({})", name),
+                    });
+                    dropped.extend(&source.defines);
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    let mut accepted = Vec::with_capacity(candidates.len());
+    for i in candidates {
+        let source = &sources[i];
+        accepted.push(source.json.clone());
+        if source.embedded {
+            scan.embedded.push(source.path.clone());
+        } else {
+            scan.loaded.push(source.path.clone());
+        }
+    }
+    scan.failures.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((accepted, scan))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::discovery::{dependency_order, Names, Source};
+    use serde_json::json;
+
+    fn source(json: &str) -> Source {
+        Source::new(String::from("test.avsc"), String::from(json), false).unwrap()
+    }
+
+    #[test]
+    fn test_names() {
+        let mut names = Names::default();
+        names.walk(
+            &json!({
+                "type": "record",
+                "name": "Unit",
+                "namespace": "insight.storage",
+                "fields": [
+                    {"name": "track", "type": "TrackInfo"},
+                    {"name": "kind", "type": {"type": "enum", "name": "Kind", "symbols": ["A"]}},
+                    {"name": "values", "type": {"type": "array", "items": "insight.x.Value"}},
+                    {"name": "extra", "type": ["null", {"type": "map", "values": "Kind"}]},
+                    {"name": "id", "type": {"type": "string", "logicalType": "uuid"}}
+                ]
+            }),
+            None,
+        );
+        assert_eq!(
+            names.defines,
+            ["insight.storage.Unit", "insight.storage.Kind"]
+        );
+        assert_eq!(
+            names.references,
+            [
+                "insight.storage.TrackInfo",
+                "insight.x.Value",
+                "insight.storage.Kind"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let sources = [
+            source(
+                r#"{"type": "record", "name": "a.A", "fields": [{"name": "b", "type": "b.B"}]}"#,
+            ),
+            source(
+                r#"{"type": "record", "name": "b.B", "fields": [{"name": "c", "type": "c.C"}]}"#,
+            ),
+            source(r#"{"type": "enum", "name": "c.C", "symbols": ["X"]}"#),
+        ];
+        assert_eq!(dependency_order(&sources), [2, 1, 0]);
+        assert!(
+            Source::new(String::from("x.avsc"), String::from("{"), false)
+                .unwrap_err()
+                .starts_with("Invalid JSON")
+        );
+        assert!(Source::new(String::from("x.avsc"), String::from(r#""string""#), false).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+use crate::envelope::SEALED_MAGIC;
+use crate::error::{ProtocolError, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Length of the ChaCha20-Poly1305 keys.
+pub const KEY_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+
+/// The prefix of a sealed envelope: the key id and the nonce the payload is sealed with.
+pub(crate) struct Sealed<'a> {
+    header: &'a [u8],
+    key_id: &'a str,
+    nonce: &'a [u8],
+}
+
+/// The seal and the envelope following it, if the buffer is sealed.
+pub(crate) fn strip_sealed(from: &[u8]) -> Result<Option<(Sealed<'_>, &[u8])>> {
+    let rest = match from.strip_prefix(&SEALED_MAGIC[..]) {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    let truncated = || ProtocolError::EnvelopeDecode(String::from("Truncated seal"));
+    let (&len, rest) = rest.split_first().ok_or_else(truncated)?;
+    let len = usize::from(len);
+    if rest.len() < len + NONCE_SIZE {
+        return Err(truncated());
+    }
+    let (key_id, rest) = rest.split_at(len);
+    let (nonce, envelope) = rest.split_at(NONCE_SIZE);
+    let key_id = std::str::from_utf8(key_id).map_err(|_| {
+        ProtocolError::EnvelopeDecode(String::from("Encryption key id is not a valid UTF-8"))
+    })?;
+    let header = &from[..SEALED_MAGIC.len() + 1 + len + NONCE_SIZE];
+    Ok(Some((
+        Sealed {
+            header,
+            key_id,
+            nonce,
+        },
+        envelope,
+    )))
+}
+
+/// The associated data binds the sealed payload to its key id, nonce, the framing of its
+/// envelope and schema.
+fn associated_data(header: &[u8], frame: &[u8], schema: &[u8]) -> Vec<u8> {
+    [header, frame, schema].concat()
+}
+
+/// ChaCha20-Poly1305 keys by key id and the choice of the key each schema is sealed with.
+///
+/// A new key is rolled out by inserting it on the receivers, then making it current on the
+/// senders; the old key is removed once nothing sealed with it is in flight. A key still current
+/// or picked for a schema is not removed.
+#[derive(Clone, Default)]
+pub struct EncryptionKeys {
+    keys: HashMap<String, ChaCha20Poly1305>,
+    current: Option<String>,
+    by_schema: HashMap<String, String>,
+}
+
+impl EncryptionKeys {
+    /// Adds the 32-byte key; the payloads sealed with any key of the set are opened.
+    pub fn insert(&mut self, key_id: &str, key: &[u8]) -> Result<()> {
+        if key_id.is_empty() || key_id.len() > usize::from(u8::MAX) {
+            return Err(ProtocolError::Encryption(format!(
+                "Key id ({}) must be 1 to 255 bytes long",
+                key_id
+            )));
+        }
+        let cipher = ChaCha20Poly1305::new_from_slice(key).map_err(|_| {
+            ProtocolError::Encryption(format!(
+                "Key ({}) must be {} bytes long, not {}",
+                key_id,
+                KEY_SIZE,
+                key.len()
+            ))
+        })?;
+        self.keys.insert(String::from(key_id), cipher);
+        Ok(())
+    }
+
+    /// Removes the key, failing while it is current or picked for a schema.
+    pub fn remove(&mut self, key_id: &str) -> Result<bool> {
+        if self.current() == Some(key_id) || self.by_schema.values().any(|k| k == key_id) {
+            return Err(ProtocolError::Encryption(format!(
+                "Key ({}) is still current or picked for a schema",
+                key_id
+            )));
+        }
+        Ok(self.keys.remove(key_id).is_some())
+    }
+
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    pub fn key_ids(&self) -> Vec<&str> {
+        let mut key_ids = self.keys.keys().map(String::as_str).collect::<Vec<_>>();
+        key_ids.sort_unstable();
+        key_ids
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Seals the payloads with the key unless their schema has a key of its own; `None` sends
+    /// them in clear text.
+    pub fn set_current(&mut self, key_id: Option<&str>) -> Result<()> {
+        self.current = key_id.map(|k| self.known(k)).transpose()?;
+        Ok(())
+    }
+
+    /// Seals the payloads of the schema with the key in place of the current one; `None`
+    /// reverts the schema to the current key.
+    pub fn set_schema_key(&mut self, schema_name: &str, key_id: Option<&str>) -> Result<()> {
+        match key_id {
+            Some(key_id) => {
+                let key_id = self.known(key_id)?;
+                self.by_schema.insert(String::from(schema_name), key_id);
+            }
+            None => {
+                self.by_schema.remove(schema_name);
+            }
+        }
+        Ok(())
+    }
+
+    /// The key id the payloads of the schema are sealed with.
+    pub fn key_for(&self, schema_name: &str) -> Option<&str> {
+        self.by_schema
+            .get(schema_name)
+            .map(String::as_str)
+            .or(self.current())
+    }
+
+    fn known(&self, key_id: &str) -> Result<String> {
+        if !self.contains(key_id) {
+            return Err(ProtocolError::UnknownEncryptionKey(String::from(key_id)));
+        }
+        Ok(String::from(key_id))
+    }
+
+    fn cipher(&self, key_id: &str) -> Result<&ChaCha20Poly1305> {
+        self.keys
+            .get(key_id)
+            .ok_or_else(|| ProtocolError::UnknownEncryptionKey(String::from(key_id)))
+    }
+
+    /// Seals the payload under a fresh nonce and returns the seal prefix with the sealed
+    /// payload; the frame header preceding the envelope, say the versioned magic and
+    /// fingerprint, and the schema identifier of the envelope are authenticated, not encrypted.
+    pub(crate) fn seal(
+        &self,
+        key_id: &str,
+        frame: &[u8],
+        schema: &[u8],
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>)> {
+        let cipher = self.cipher(key_id)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut header = Vec::with_capacity(SEALED_MAGIC.len() + 1 + key_id.len() + NONCE_SIZE);
+        header.extend_from_slice(&SEALED_MAGIC);
+        header.push(key_id.len() as u8);
+        header.extend_from_slice(key_id.as_bytes());
+        header.extend_from_slice(&nonce);
+        let sealed = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: payload,
+                    aad: &associated_data(&header, frame, schema),
+                },
+            )
+            .map_err(|_| ProtocolError::Encryption(format!("Failed to seal ({})", key_id)))?;
+        Ok((header, sealed))
+    }
+
+    /// Opens the payload of the envelope following the seal.
+    pub(crate) fn open(
+        &self,
+        sealed: &Sealed,
+        frame: &[u8],
+        schema: &[u8],
+        payload: &[u8],
+    ) -> Result<Vec<u8>> {
+        self.cipher(sealed.key_id)?
+            .decrypt(
+                Nonce::from_slice(sealed.nonce),
+                Payload {
+                    msg: payload,
+                    aad: &associated_data(sealed.header, frame, schema),
+                },
+            )
+            .map_err(|_| {
+                ProtocolError::Encryption(format!(
+                    "The payload sealed with the key ({}) is forged or corrupted",
+                    sealed.key_id
+                ))
+            })
+    }
+}
+
+impl fmt::Debug for EncryptionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKeys")
+            .field("keys", &self.key_ids())
+            .field("current", &self.current)
+            .field("by_schema", &self.by_schema)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encryption::{strip_sealed, EncryptionKeys};
+    use crate::error::ProtocolError;
+
+    const VIDEO: &str = "insight.Video";
+
+    #[test]
+    fn test_seal_and_open() {
+        let mut keys = EncryptionKeys::default();
+        keys.insert("video-1", &[7; 32]).unwrap();
+        let (header, mut payload) = keys.seal("video-1", b"frame", b"schema", b"datum").unwrap();
+        assert_ne!(payload[..5], b"datum"[..]);
+
+        let mut framed = header.clone();
+        framed.extend_from_slice(b"envelope");
+        let (sealed, envelope) = strip_sealed(&framed).unwrap().unwrap();
+        assert_eq!(envelope, b"envelope");
+        assert_eq!(
+            keys.open(&sealed, b"frame", b"schema", &payload).unwrap(),
+            b"datum"
+        );
+        assert!(matches!(
+            keys.open(&sealed, b"frame", b"other", &payload),
+            Err(ProtocolError::Encryption(_))
+        ));
+        assert!(matches!(
+            keys.open(&sealed, b"other", b"schema", &payload),
+            Err(ProtocolError::Encryption(_))
+        ));
+
+        payload[0] ^= 0x01;
+        assert!(keys.open(&sealed, b"frame", b"schema", &payload).is_err());
+        assert!(strip_sealed(&header[..6]).is_err());
+        assert!(strip_sealed(b"envelope").unwrap().is_none());
+
+        let mut rotated = EncryptionKeys::default();
+        rotated.insert("video-2", &[8; 32]).unwrap();
+        assert_eq!(
+            rotated.open(&sealed, b"frame", b"schema", &payload),
+            Err(ProtocolError::UnknownEncryptionKey(String::from("video-1")))
+        );
+    }
+
+    #[test]
+    fn test_key_choice() {
+        let mut keys = EncryptionKeys::default();
+        assert!(matches!(
+            keys.insert("short", &[0; 16]),
+            Err(ProtocolError::Encryption(_))
+        ));
+        assert!(keys.set_current(Some("video-1")).is_err());
+
+        keys.insert("video-1", &[7; 32]).unwrap();
+        keys.insert("video-2", &[8; 32]).unwrap();
+        keys.set_current(Some("video-1")).unwrap();
+        keys.set_schema_key(VIDEO, Some("video-2")).unwrap();
+        assert_eq!(keys.key_for(VIDEO), Some("video-2"));
+        assert_eq!(keys.key_for("insight.Other"), Some("video-1"));
+
+        keys.set_schema_key(VIDEO, None).unwrap();
+        keys.set_current(None).unwrap();
+        assert_eq!(keys.key_for(VIDEO), None);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let mut keys = EncryptionKeys::default();
+        keys.insert("video-1", &[7; 32]).unwrap();
+        keys.insert("video-2", &[8; 32]).unwrap();
+        keys.set_current(Some("video-1")).unwrap();
+        keys.set_schema_key(VIDEO, Some("video-1")).unwrap();
+
+        keys.set_current(Some("video-2")).unwrap();
+        assert!(matches!(
+            keys.remove("video-1"),
+            Err(ProtocolError::Encryption(_))
+        ));
+        keys.set_schema_key(VIDEO, Some("video-2")).unwrap();
+        assert_eq!(keys.remove("video-1"), Ok(true));
+        assert_eq!(keys.remove("video-1"), Ok(false));
+        assert!(keys.remove("video-2").is_err());
+        assert_eq!(keys.key_for(VIDEO), Some("video-2"));
+        assert_eq!(keys.key_ids(), ["video-2"]);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::compression::Compression;
+use crate::error::{ProtocolError, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Leading bytes of a versioned envelope.
+///
+/// A `MessageEnvelope` datum starts with the zigzag-encoded length of the schema name, so its
+/// first byte is always even and never collides with the odd magic byte.
+pub const VERSIONED_ENVELOPE_MAGIC: [u8; 2] = [0xC5, 0x01];
+/// Leading bytes of a compact envelope.
+pub const COMPACT_ENVELOPE_MAGIC: [u8; 2] = [0xC5, 0x02];
+/// Leading bytes of the headers block which precedes any envelope but the single-object one.
+pub const HEADERS_MAGIC: [u8; 2] = [0xC5, 0x03];
+/// Leading bytes of a compressed frame, followed by the codec flag.
+pub const COMPRESSED_MAGIC: [u8; 2] = [0xC5, 0x04];
+/// Leading bytes of a `MessageBatch` frame.
+pub const BATCH_MAGIC: [u8; 2] = [0xC5, 0x05];
+/// Leading bytes of a fragment of an oversized message.
+pub const FRAGMENT_MAGIC: [u8; 2] = [0xC5, 0x06];
+/// Leading bytes of a checksummed envelope, followed by the CRC32C of the envelope.
+pub const CHECKSUM_MAGIC: [u8; 2] = [0xC5, 0x07];
+/// Leading bytes of a signed message, followed by the key id and the HMAC-SHA256 tag.
+pub const SIGNED_MAGIC: [u8; 2] = [0xC5, 0x08];
+/// Leading bytes of an envelope with a sealed payload, followed by the key id and the nonce.
+pub const SEALED_MAGIC: [u8; 2] = [0xC5, 0x09];
+/// Leading bytes of the Avro single-object encoding.
+pub const SINGLE_OBJECT_MAGIC: [u8; 2] = [0xC3, 0x01];
+
+const FINGERPRINT_SIZE: usize = 8;
+const CHECKSUM_SIZE: usize = 4;
+
+pub const MESSAGE_BATCH_SCHEMA: &str = "insight.transport.MessageBatch";
+
+/// Schema of the batch frame; every item is a message framed as if it were sent alone.
+pub const MESSAGE_BATCH_SCHEMA_JSON: &str = r#"{
+    "type": "record",
+    "name": "MessageBatch",
+    "namespace": "insight.transport",
+    "fields": [
+        {"name": "messages", "type": {"type": "array", "items": "bytes"}}
+    ]
+}"#;
+
+/// How the messages are framed when encoded; every framing is recognized when decoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub enum EnvelopeFormat {
+    /// The `MessageEnvelope` naming the schema after its `.avsc` file, readable by every
+    /// version of the library.
+    #[default]
+    Named,
+    /// The `MessageEnvelope` prefixed with the fingerprint of the writer schema, so the reader
+    /// resolves the payload written with an older or newer version of the schema.
+    Versioned,
+    /// The `MessageEnvelope` carrying the fingerprint of the writer schema in place of its name,
+    /// which saves the 40-60 bytes of the name on every message.
+    Compact,
+    /// The Avro single-object encoding: the datum prefixed with the fingerprint of its schema,
+    /// understood by the stock Avro libraries.
+    SingleObject,
+    /// The `MessageEnvelope` naming the schema by its Avro full name, which the releases
+    /// looking the schemas up by file name do not understand.
+    FullName,
+}
+
+/// Envelope framing recognized in the incoming buffer.
+pub(crate) enum Framing<'a> {
+    Named(&'a [u8]),
+    Versioned {
+        fingerprint: u64,
+        envelope: &'a [u8],
+    },
+    Compact(&'a [u8]),
+    SingleObject {
+        fingerprint: u64,
+        datum: &'a [u8],
+    },
+}
+
+pub(crate) fn detect_framing(from: &[u8]) -> Result<Framing<'_>> {
+    if let Some(rest) = from.strip_prefix(&VERSIONED_ENVELOPE_MAGIC[..]) {
+        let (fingerprint, envelope) = split_fingerprint(rest)?;
+        Ok(Framing::Versioned {
+            fingerprint,
+            envelope,
+        })
+    } else if let Some(envelope) = from.strip_prefix(&COMPACT_ENVELOPE_MAGIC[..]) {
+        Ok(Framing::Compact(envelope))
+    } else if let Some(rest) = from.strip_prefix(&SINGLE_OBJECT_MAGIC[..]) {
+        let (fingerprint, datum) = split_fingerprint(rest)?;
+        Ok(Framing::SingleObject { fingerprint, datum })
+    } else {
+        Ok(Framing::Named(from))
+    }
+}
+
+pub(crate) fn write_versioned(fingerprint: u64, envelope: &[u8]) -> Vec<u8> {
+    write_fingerprinted(&VERSIONED_ENVELOPE_MAGIC, fingerprint, envelope)
+}
+
+pub(crate) fn write_single_object(fingerprint: u64, datum: &[u8]) -> Vec<u8> {
+    write_fingerprinted(&SINGLE_OBJECT_MAGIC, fingerprint, datum)
+}
+
+fn write_fingerprinted(magic: &[u8], fingerprint: u64, body: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(magic.len() + FINGERPRINT_SIZE + body.len());
+    buf.extend_from_slice(magic);
+    buf.extend_from_slice(&fingerprint.to_le_bytes());
+    buf.extend_from_slice(body);
+    buf
+}
+
+/// The headers datum and the framed message following it, if the buffer starts with headers.
+pub(crate) fn strip_headers(from: &[u8]) -> Option<&[u8]> {
+    from.strip_prefix(&HEADERS_MAGIC[..])
+}
+
+/// The `MessageBatch` datum, if the buffer is a batch.
+pub(crate) fn strip_batch(from: &[u8]) -> Option<&[u8]> {
+    from.strip_prefix(&BATCH_MAGIC[..])
+}
+
+pub(crate) fn write_headers(headers: &[u8], framed: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADERS_MAGIC.len() + headers.len() + framed.len());
+    buf.extend_from_slice(&HEADERS_MAGIC);
+    buf.extend_from_slice(headers);
+    buf.extend_from_slice(framed);
+    buf
+}
+
+/// The codec and the compressed framed message, if the buffer is compressed.
+pub(crate) fn strip_compressed(from: &[u8]) -> Result<Option<(Compression, &[u8])>> {
+    match from.strip_prefix(&COMPRESSED_MAGIC[..]) {
+        Some([id, data @ ..]) => Ok(Some((Compression::from_id(*id)?, data))),
+        Some([]) => Err(ProtocolError::EnvelopeDecode(String::from(
+            "Truncated compression codec",
+        ))),
+        None => Ok(None),
+    }
+}
+
+pub(crate) fn write_compressed(compression: Compression, data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(COMPRESSED_MAGIC.len() + 1 + data.len());
+    buf.extend_from_slice(&COMPRESSED_MAGIC);
+    buf.push(compression.id());
+    buf.extend_from_slice(data);
+    buf
+}
+
+pub(crate) fn write_checksummed(framed: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(CHECKSUM_MAGIC.len() + CHECKSUM_SIZE + framed.len());
+    buf.extend_from_slice(&CHECKSUM_MAGIC);
+    buf.extend_from_slice(&crc32c::crc32c(framed).to_le_bytes());
+    buf.extend_from_slice(framed);
+    buf
+}
+
+/// The framed envelope following a checksum which matches it, the buffer itself if it carries
+/// no checksum.
+pub(crate) fn verify_checksum(from: &[u8]) -> Result<&[u8]> {
+    let rest = match from.strip_prefix(&CHECKSUM_MAGIC[..]) {
+        Some(rest) => rest,
+        None => return Ok(from),
+    };
+    if rest.len() < CHECKSUM_SIZE {
+        return Err(ProtocolError::EnvelopeDecode(String::from(
+            "Truncated checksum",
+        )));
+    }
+    let (checksum, framed) = rest.split_at(CHECKSUM_SIZE);
+    let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+    let actual = crc32c::crc32c(framed);
+    if expected != actual {
+        return Err(ProtocolError::ChecksumMismatch { expected, actual });
+    }
+    Ok(framed)
+}
+
+pub(crate) fn write_compact(envelope: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(COMPACT_ENVELOPE_MAGIC.len() + envelope.len());
+    buf.extend_from_slice(&COMPACT_ENVELOPE_MAGIC);
+    buf.extend_from_slice(envelope);
+    buf
+}
+
+/// Fingerprint stored in the `schema` field of a compact envelope.
+pub(crate) fn read_fingerprint(from: &[u8]) -> Result<u64> {
+    match split_fingerprint(from)? {
+        (fingerprint, []) => Ok(fingerprint),
+        _ => Err(ProtocolError::EnvelopeDecode(String::from(
+            "Schema fingerprint must be 8 bytes long",
+        ))),
+    }
+}
+
+fn split_fingerprint(from: &[u8]) -> Result<(u64, &[u8])> {
+    if from.len() < FINGERPRINT_SIZE {
+        return Err(ProtocolError::EnvelopeDecode(String::from(
+            "Truncated schema fingerprint",
+        )));
+    }
+    let (fingerprint, rest) = from.split_at(FINGERPRINT_SIZE);
+    let mut buf = [0; FINGERPRINT_SIZE];
+    buf.copy_from_slice(fingerprint);
+    Ok((u64::from_le_bytes(buf), rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::envelope::{
+        detect_framing, read_fingerprint, verify_checksum, write_checksummed, write_compact,
+        write_single_object, write_versioned, Framing,
+    };
+    use crate::error::ProtocolError;
+
+    #[test]
+    fn test_detect_framing() {
+        let bytes = write_versioned(0x0102_0304_0506_0708, &[2, 4]);
+        assert!(matches!(
+            detect_framing(&bytes),
+            Ok(Framing::Versioned {
+                fingerprint: 0x0102_0304_0506_0708,
+                envelope: [2, 4]
+            })
+        ));
+        assert!(matches!(
+            detect_framing(&[2, 4]),
+            Ok(Framing::Named([2, 4]))
+        ));
+        assert!(matches!(
+            detect_framing(&bytes[..5]),
+            Err(ProtocolError::EnvelopeDecode(_))
+        ));
+        assert!(matches!(
+            detect_framing(&write_compact(&[2, 4])),
+            Ok(Framing::Compact([2, 4]))
+        ));
+        assert_eq!(
+            write_single_object(1, &[2]),
+            [0xC3, 0x01, 1, 0, 0, 0, 0, 0, 0, 0, 2]
+        );
+        assert!(matches!(
+            detect_framing(&write_single_object(1, &[2])),
+            Ok(Framing::SingleObject {
+                fingerprint: 1,
+                datum: [2]
+            })
+        ));
+        assert_eq!(read_fingerprint(&7u64.to_le_bytes()), Ok(7));
+        assert!(read_fingerprint(&[0; 9]).is_err());
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut bytes = write_checksummed(b"123456789");
+        assert_eq!(bytes[2..6], 0xe306_9283u32.to_le_bytes());
+        assert_eq!(verify_checksum(&bytes), Ok(&b"123456789"[..]));
+        assert_eq!(verify_checksum(&[2, 4]), Ok(&[2, 4][..]));
+
+        bytes[8] ^= 0x10;
+        assert!(matches!(
+            verify_checksum(&bytes),
+            Err(ProtocolError::ChecksumMismatch {
+                expected: 0xe306_9283,
+                ..
+            })
+        ));
+        assert!(verify_checksum(&bytes[..4]).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use std::fmt;
+
+/// Errors produced by every encode/decode path of the protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    /// The outer `MessageEnvelope` cannot be decoded.
+    EnvelopeDecode(String),
+    /// The inner payload cannot be decoded with the schema named in the envelope.
+    PayloadDecode { schema: String, reason: String },
+    /// The schema is not present in the catalog.
+    UnknownSchema(String),
+    /// The writer's version of the schema is not present in the catalog.
+    UnknownSchemaVersion { schema: String, fingerprint: u64 },
+    /// No version of any schema in the catalog has the fingerprint.
+    UnknownFingerprint(u64),
+    /// The schema is present in the catalog, but no decoder is registered for it.
+    NoDecoder(String),
+    /// The message carries a schema other than the one the target type expects.
+    SchemaMismatch { expected: String, found: String },
+    /// The Avro value is not a record of the expected layout.
+    RecordMismatch(String),
+    /// The record field has an unexpected type.
+    FieldMismatch { schema: String, field: String },
+    /// The record lacks the field and the decoder has no fallback for it.
+    MissingField { schema: String, field: String },
+    /// The enum symbol is not known to the protocol.
+    UnknownEnumSymbol { enum_name: String, symbol: String },
+    /// The value does not fit into the target type.
+    ValueOutOfRange { field: String, value: String },
+    /// The schema catalog cannot be loaded.
+    SchemaLoad(String),
+    /// The value cannot be serialized with its schema.
+    Encode(String),
+    /// The fragment is malformed, inconsistent with its transfer or over the reassembly limits.
+    Fragment(String),
+    /// The frame length exceeds the maximum frame size of the stream.
+    FrameTooLarge { size: u64, max: usize },
+    /// The byte stream failed or ended in the middle of a frame.
+    Stream(String),
+    /// The received data exceeds one of the `DecodeLimits`.
+    LimitExceeded { limit: String, value: u64, max: u64 },
+    /// The CRC32C of the received envelope differs from the one it carries.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The signature of the message is missing or invalid where one is required, or the signing
+    /// key is unusable.
+    Signature(String),
+    /// The payload cannot be sealed or opened, or the encryption key is unusable.
+    Encryption(String),
+    /// No encryption key with the id is known.
+    UnknownEncryptionKey(String),
+}
+
+pub type Result<T> = std::result::Result<T, ProtocolError>;
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::EnvelopeDecode(reason) => {
+                write!(f, "Failed to decode the message envelope: {}", reason)
+            }
+            ProtocolError::PayloadDecode { schema, reason } => write!(
+                f,
+                "Failed to decode the payload with the schema ({}): {}",
+                schema, reason
+            ),
+            ProtocolError::UnknownSchema(schema) => write!(
+                f,
+                "No valid schema found in schema catalog for the schema ({})",
+                schema
+            ),
+            ProtocolError::UnknownSchemaVersion {
+                schema,
+                fingerprint,
+            } => write!(
+                f,
+                "Unknown version ({:016x}) of the schema ({})",
+                fingerprint, schema
+            ),
+            ProtocolError::UnknownFingerprint(fingerprint) => {
+                write!(f, "No schema with the fingerprint ({:016x})", fingerprint)
+            }
+            ProtocolError::NoDecoder(schema) => {
+                write!(f, "No decoder registered for the schema ({})", schema)
+            }
+            ProtocolError::SchemaMismatch { expected, found } => write!(
+                f,
+                "Unexpected schema ({}), the schema ({}) is expected",
+                found, expected
+            ),
+            ProtocolError::RecordMismatch(schema) => {
+                write!(f, "Unable to match AVRO Record to the schema ({})", schema)
+            }
+            ProtocolError::FieldMismatch { schema, field } => write!(
+                f,
+                "Unable to match the field ({}) of the schema ({})",
+                field, schema
+            ),
+            ProtocolError::MissingField { schema, field } => write!(
+                f,
+                "The field ({}) is missing from the record of the schema ({})",
+                field, schema
+            ),
+            ProtocolError::UnknownEnumSymbol { enum_name, symbol } => {
+                write!(f, "Unknown symbol ({}) of the enum ({})", symbol, enum_name)
+            }
+            ProtocolError::ValueOutOfRange { field, value } => {
+                write!(
+                    f,
+                    "Value ({}) of the field ({}) is out of range",
+                    value, field
+                )
+            }
+            ProtocolError::SchemaLoad(reason) => {
+                write!(f, "Failed to load the schemas: {}", reason)
+            }
+            ProtocolError::Encode(reason) => write!(f, "Failed to encode the message: {}", reason),
+            ProtocolError::Fragment(reason) => {
+                write!(f, "Failed to reassemble the message: {}", reason)
+            }
+            ProtocolError::FrameTooLarge { size, max } => write!(
+                f,
+                "Frame of {} bytes exceeds the maximum frame size of {} bytes",
+                size, max
+            ),
+            ProtocolError::Stream(reason) => write!(f, "Failed to transfer the frame: {}", reason),
+            ProtocolError::LimitExceeded { limit, value, max } => write!(
+                f,
+                "The {} ({}) exceeds the decode limit ({})",
+                limit, value, max
+            ),
+            ProtocolError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Envelope checksum mismatch: expected {:08x}, computed {:08x}",
+                expected, actual
+            ),
+            ProtocolError::Signature(reason) => {
+                write!(f, "Failed to authenticate the message: {}", reason)
+            }
+            ProtocolError::Encryption(reason) => {
+                write!(f, "Failed to encrypt or decrypt the payload: {}", reason)
+            }
+            ProtocolError::UnknownEncryptionKey(key_id) => {
+                write!(f, "Unknown encryption key ({})", key_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+#[cfg(feature = "python")]
+pub use python::*;
+
+#[cfg(feature = "python")]
+mod python {
+    use super::ProtocolError;
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+    use pyo3::prelude::*;
+
+    create_exception!(protocol, ProtocolException, PyException);
+    create_exception!(protocol, EnvelopeDecodeError, ProtocolException);
+    create_exception!(protocol, PayloadDecodeError, ProtocolException);
+    create_exception!(protocol, UnknownSchemaError, ProtocolException);
+    create_exception!(protocol, UnknownSchemaVersionError, UnknownSchemaError);
+    create_exception!(protocol, NoDecoderError, ProtocolException);
+    create_exception!(protocol, SchemaMismatchError, ProtocolException);
+    create_exception!(protocol, FieldMismatchError, ProtocolException);
+    create_exception!(protocol, MissingFieldError, FieldMismatchError);
+    create_exception!(protocol, UnknownEnumSymbolError, ProtocolException);
+    create_exception!(protocol, ValueOutOfRangeError, ProtocolException);
+    create_exception!(protocol, SchemaLoadError, ProtocolException);
+    create_exception!(protocol, EncodeError, ProtocolException);
+    create_exception!(protocol, FragmentError, ProtocolException);
+    create_exception!(protocol, StreamError, ProtocolException);
+    create_exception!(protocol, FrameTooLargeError, StreamError);
+    create_exception!(protocol, LimitExceededError, ProtocolException);
+    create_exception!(protocol, ChecksumMismatchError, ProtocolException);
+    create_exception!(protocol, SignatureError, ProtocolException);
+    create_exception!(protocol, EncryptionError, ProtocolException);
+    create_exception!(protocol, UnknownEncryptionKeyError, EncryptionError);
+
+    impl From<ProtocolError> for PyErr {
+        fn from(e: ProtocolError) -> PyErr {
+            let message = e.to_string();
+            match e {
+                ProtocolError::EnvelopeDecode(_) => EnvelopeDecodeError::new_err(message),
+                ProtocolError::PayloadDecode { .. } => PayloadDecodeError::new_err(message),
+                ProtocolError::UnknownSchema(_) => UnknownSchemaError::new_err(message),
+                ProtocolError::UnknownSchemaVersion { .. }
+                | ProtocolError::UnknownFingerprint(_) => {
+                    UnknownSchemaVersionError::new_err(message)
+                }
+                ProtocolError::NoDecoder(_) => NoDecoderError::new_err(message),
+                ProtocolError::SchemaMismatch { .. } => SchemaMismatchError::new_err(message),
+                ProtocolError::RecordMismatch(_) | ProtocolError::FieldMismatch { .. } => {
+                    FieldMismatchError::new_err(message)
+                }
+                ProtocolError::MissingField { .. } => MissingFieldError::new_err(message),
+                ProtocolError::UnknownEnumSymbol { .. } => UnknownEnumSymbolError::new_err(message),
+                ProtocolError::ValueOutOfRange { .. } => ValueOutOfRangeError::new_err(message),
+                ProtocolError::SchemaLoad(_) => SchemaLoadError::new_err(message),
+                ProtocolError::Encode(_) => EncodeError::new_err(message),
+                ProtocolError::Fragment(_) => FragmentError::new_err(message),
+                ProtocolError::FrameTooLarge { .. } => FrameTooLargeError::new_err(message),
+                ProtocolError::Stream(_) => StreamError::new_err(message),
+                ProtocolError::LimitExceeded { .. } => LimitExceededError::new_err(message),
+                ProtocolError::ChecksumMismatch { .. } => ChecksumMismatchError::new_err(message),
+                ProtocolError::Signature(_) => SignatureError::new_err(message),
+                ProtocolError::Encryption(_) => EncryptionError::new_err(message),
+                ProtocolError::UnknownEncryptionKey(_) => {
+                    UnknownEncryptionKeyError::new_err(message)
+                }
+            }
+        }
+    }
+
+    pub fn register_exceptions(py: Python, m: &PyModule) -> PyResult<()> {
+        m.add("ProtocolException", py.get_type::<ProtocolException>())?;
+        m.add("EnvelopeDecodeError", py.get_type::<EnvelopeDecodeError>())?;
+        m.add("PayloadDecodeError", py.get_type::<PayloadDecodeError>())?;
+        m.add("UnknownSchemaError", py.get_type::<UnknownSchemaError>())?;
+        m.add(
+            "UnknownSchemaVersionError",
+            py.get_type::<UnknownSchemaVersionError>(),
+        )?;
+        m.add("NoDecoderError", py.get_type::<NoDecoderError>())?;
+        m.add("SchemaMismatchError", py.get_type::<SchemaMismatchError>())?;
+        m.add("FieldMismatchError", py.get_type::<FieldMismatchError>())?;
+        m.add("MissingFieldError", py.get_type::<MissingFieldError>())?;
+        m.add(
+            "UnknownEnumSymbolError",
+            py.get_type::<UnknownEnumSymbolError>(),
+        )?;
+        m.add(
+            "ValueOutOfRangeError",
+            py.get_type::<ValueOutOfRangeError>(),
+        )?;
+        m.add("SchemaLoadError", py.get_type::<SchemaLoadError>())?;
+        m.add("EncodeError", py.get_type::<EncodeError>())?;
+        m.add("FragmentError", py.get_type::<FragmentError>())?;
+        m.add("StreamError", py.get_type::<StreamError>())?;
+        m.add("FrameTooLargeError", py.get_type::<FrameTooLargeError>())?;
+        m.add("LimitExceededError", py.get_type::<LimitExceededError>())?;
+        m.add(
+            "ChecksumMismatchError",
+            py.get_type::<ChecksumMismatchError>(),
+        )?;
+        m.add("SignatureError", py.get_type::<SignatureError>())?;
+        m.add("EncryptionError", py.get_type::<EncryptionError>())?;
+        m.add(
+            "UnknownEncryptionKeyError",
+            py.get_type::<UnknownEncryptionKeyError>(),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use avro_rs::rabin::Rabin;
+use avro_rs::Schema;
+
+/// Fingerprint of the Parsing Canonical Form of the schema: the 64-bit Rabin fingerprint
+/// (CRC-64-AVRO) defined by the Avro specification.
+pub fn schema_fingerprint(schema: &Schema) -> u64 {
+    let fingerprint = schema.fingerprint::<Rabin>().bytes;
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&fingerprint);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fingerprint::schema_fingerprint;
+    use avro_rs::Schema;
+
+    #[test]
+    fn test_schema_fingerprint() {
+        let fingerprint = |json: &str| schema_fingerprint(&Schema::parse_str(json).unwrap());
+        assert_eq!(fingerprint(r#""null""#), 0x63dd_24e7_cc25_8f8a);
+        assert_eq!(fingerprint(r#""int""#), 0x7275_d51a_3f39_5c8f);
+        assert_eq!(
+            fingerprint(r#"{"type": "fixed", "name": "foo", "size": 15}"#),
+            fingerprint(r#"{"name":"foo","type":"fixed","size":15}"#)
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use crate::envelope::FRAGMENT_MAGIC;
+use crate::error::{ProtocolError, Result};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const TRANSFER_ID_SIZE: usize = 16;
+/// Magic, transfer id, fragment index and fragment count.
+pub const FRAGMENT_HEADER_SIZE: usize = FRAGMENT_MAGIC.len() + TRANSFER_ID_SIZE + 4 + 4;
+/// Most fragments a message is split into.
+pub const MAX_FRAGMENT_COUNT: u32 = 1 << 16;
+/// Bytes charged against the reassembly limit for every fragment held on top of its chunk,
+/// so that a flood of tiny fragments cannot grow the bookkeeping unbounded.
+pub const FRAGMENT_OVERHEAD: usize = FRAGMENT_HEADER_SIZE;
+
+/// One chunk of an encoded message split by the `Fragmenter`.
+#[derive(Debug, PartialEq)]
+struct Fragment<'a> {
+    transfer_id: Uuid,
+    index: u32,
+    count: u32,
+    chunk: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    /// The fragment, `None` if the buffer holds a whole message.
+    fn parse(from: &'a [u8]) -> Result<Option<Fragment<'a>>> {
+        let rest = match from.strip_prefix(&FRAGMENT_MAGIC[..]) {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        if rest.len() < FRAGMENT_HEADER_SIZE - FRAGMENT_MAGIC.len() {
+            return Err(ProtocolError::Fragment(String::from(
+                "Truncated fragment header",
+            )));
+        }
+        let (transfer_id, rest) = rest.split_at(TRANSFER_ID_SIZE);
+        let (index, rest) = rest.split_at(4);
+        let (count, chunk) = rest.split_at(4);
+        let fragment = Fragment {
+            transfer_id: Uuid::from_slice(transfer_id)
+                .map_err(|e| ProtocolError::Fragment(e.to_string()))?,
+            index: u32::from_le_bytes([index[0], index[1], index[2], index[3]]),
+            count: u32::from_le_bytes([count[0], count[1], count[2], count[3]]),
+            chunk,
+        };
+        if fragment.count > MAX_FRAGMENT_COUNT {
+            return Err(ProtocolError::Fragment(format!(
+                "Fragment count ({}) exceeds the limit of {}",
+                fragment.count, MAX_FRAGMENT_COUNT
+            )));
+        }
+        if fragment.index >= fragment.count {
+            return Err(ProtocolError::Fragment(format!(
+                "Fragment index ({}) is out of the fragment count ({})",
+                fragment.index, fragment.count
+            )));
+        }
+        if fragment.chunk.is_empty() {
+            return Err(ProtocolError::Fragment(String::from("Empty fragment")));
+        }
+        Ok(Some(fragment))
+    }
+
+    /// Bytes the fragment is charged for while it waits for the rest of the transfer.
+    fn charge(&self) -> usize {
+        self.chunk.len() + FRAGMENT_OVERHEAD
+    }
+
+    fn write(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(FRAGMENT_HEADER_SIZE + self.chunk.len());
+        buf.extend_from_slice(&FRAGMENT_MAGIC);
+        buf.extend_from_slice(self.transfer_id.as_bytes());
+        buf.extend_from_slice(&self.index.to_le_bytes());
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        buf.extend_from_slice(self.chunk);
+        buf
+    }
+}
+
+/// Splits the encoded messages larger than the broker accepts into numbered fragments.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct Fragmenter {
+    max_fragment_size: usize,
+}
+
+impl Fragmenter {
+    /// No fragment, header included, is longer than `max_fragment_size` bytes.
+    pub fn new(max_fragment_size: usize) -> Result<Self> {
+        if max_fragment_size <= FRAGMENT_HEADER_SIZE {
+            return Err(ProtocolError::Fragment(format!(
+                "Fragment size ({}) must exceed the fragment header ({})",
+                max_fragment_size, FRAGMENT_HEADER_SIZE
+            )));
+        }
+        Ok(Fragmenter { max_fragment_size })
+    }
+
+    pub fn max_fragment_size(&self) -> usize {
+        self.max_fragment_size
+    }
+
+    /// The fragments of the message, or the message itself if it fits into one.
+    pub fn split(&self, message: &[u8]) -> Result<Vec<Vec<u8>>> {
+        if message.len() <= self.max_fragment_size {
+            return Ok(vec![message.to_vec()]);
+        }
+        let chunks = message.chunks(self.max_fragment_size - FRAGMENT_HEADER_SIZE);
+        let count = u32::try_from(chunks.len())
+            .ok()
+            .filter(|count| *count <= MAX_FRAGMENT_COUNT)
+            .ok_or_else(|| {
+                ProtocolError::Fragment(format!(
+                    "Message of {} bytes needs too many fragments",
+                    message.len()
+                ))
+            })?;
+        let transfer_id = Uuid::new_v4();
+        Ok(chunks
+            .enumerate()
+            .map(|(index, chunk)| {
+                Fragment {
+                    transfer_id,
+                    index: index as u32,
+                    count,
+                    chunk,
+                }
+                .write()
+            })
+            .collect())
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Fragmenter {
+    #[new]
+    fn py_new(max_fragment_size: usize) -> PyResult<Self> {
+        Ok(Self::new(max_fragment_size)?)
+    }
+
+    #[getter(max_fragment_size)]
+    fn py_max_fragment_size(&self) -> usize {
+        self.max_fragment_size
+    }
+
+    #[pyo3(name = "split")]
+    fn py_split(&self, message: Vec<u8>) -> PyResult<Vec<Vec<u8>>> {
+        Ok(self.split(&message)?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+#[derive(Debug)]
+struct Transfer {
+    started: Instant,
+    count: u32,
+    size: usize,
+    chunks: BTreeMap<u32, Vec<u8>>,
+}
+
+/// Collects the fragments and yields the original message once all of them arrive.
+///
+/// A transfer not completed within `timeout` of its first fragment is dropped. When the
+/// fragments held exceed `max_pending_bytes` the oldest transfers are dropped to make room;
+/// every fragment held counts `FRAGMENT_OVERHEAD` bytes on top of its chunk.
+#[derive(Debug)]
+#[cfg_attr(feature = "python", pyclass)]
+pub struct Reassembler {
+    timeout: Duration,
+    max_pending_bytes: usize,
+    pending_bytes: usize,
+    transfers: HashMap<Uuid, Transfer>,
+}
+
+impl Reassembler {
+    pub fn new(timeout: Duration, max_pending_bytes: usize) -> Self {
+        Reassembler {
+            timeout,
+            max_pending_bytes,
+            pending_bytes: 0,
+            transfers: HashMap::default(),
+        }
+    }
+
+    /// The reassembled message once the last fragment arrives; a buffer which is not a
+    /// fragment is returned as is.
+    pub fn push(&mut self, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.push_at(data, Instant::now())
+    }
+
+    pub fn push_at(&mut self, data: &[u8], now: Instant) -> Result<Option<Vec<u8>>> {
+        self.expire_at(now);
+        let fragment = match Fragment::parse(data)? {
+            Some(fragment) => fragment,
+            None => return Ok(Some(data.to_vec())),
+        };
+        if let Some(transfer) = self.transfers.get(&fragment.transfer_id) {
+            if transfer.count != fragment.count {
+                self.drop_transfer(&fragment.transfer_id);
+                return Err(ProtocolError::Fragment(format!(
+                    "Fragment count of the transfer ({}) changed",
+                    fragment.transfer_id
+                )));
+            }
+            if transfer.chunks.contains_key(&fragment.index) {
+                return Ok(None);
+            }
+        }
+        self.make_room(&fragment)?;
+
+        let transfer = self
+            .transfers
+            .entry(fragment.transfer_id)
+            .or_insert_with(|| Transfer {
+                started: now,
+                count: fragment.count,
+                size: 0,
+                chunks: BTreeMap::default(),
+            });
+        transfer.size += fragment.charge();
+        transfer
+            .chunks
+            .insert(fragment.index, fragment.chunk.to_vec());
+        self.pending_bytes += fragment.charge();
+        if transfer.chunks.len() < transfer.count as usize {
+            return Ok(None);
+        }
+        let transfer = self.drop_transfer(&fragment.transfer_id).unwrap();
+        Ok(Some(transfer.chunks.into_values().flatten().collect()))
+    }
+
+    /// Drops the transfers which timed out and returns their number.
+    pub fn expire(&mut self) -> usize {
+        self.expire_at(Instant::now())
+    }
+
+    pub fn expire_at(&mut self, now: Instant) -> usize {
+        let expired: Vec<Uuid> = self
+            .transfers
+            .iter()
+            .filter(|(_, t)| now.saturating_duration_since(t.started) >= self.timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            self.drop_transfer(id);
+        }
+        expired.len()
+    }
+
+    /// Number of the incomplete transfers.
+    pub fn pending_transfers(&self) -> usize {
+        self.transfers.len()
+    }
+
+    /// Bytes held by the fragments of the incomplete transfers, overhead included.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    fn make_room(&mut self, fragment: &Fragment) -> Result<()> {
+        while self.pending_bytes + fragment.charge() > self.max_pending_bytes {
+            let oldest = self
+                .transfers
+                .iter()
+                .filter(|(id, _)| **id != fragment.transfer_id)
+                .min_by_key(|(_, t)| t.started)
+                .map(|(id, _)| *id);
+            match oldest {
+                Some(id) => {
+                    self.drop_transfer(&id);
+                }
+                None => {
+                    self.drop_transfer(&fragment.transfer_id);
+                    return Err(ProtocolError::Fragment(format!(
+                        "Transfer ({}) exceeds the reassembly limit of {} bytes",
+                        fragment.transfer_id, self.max_pending_bytes
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_transfer(&mut self, id: &Uuid) -> Option<Transfer> {
+        let transfer = self.transfers.remove(id)?;
+        self.pending_bytes -= transfer.size;
+        Some(transfer)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Reassembler {
+    #[new]
+    fn py_new(timeout_ms: u64, max_pending_bytes: usize) -> Self {
+        Self::new(Duration::from_millis(timeout_ms), max_pending_bytes)
+    }
+
+    #[pyo3(name = "push")]
+    fn py_push(&mut self, data: Vec<u8>) -> PyResult<Option<Vec<u8>>> {
+        Ok(self.push(&data)?)
+    }
+
+    #[pyo3(name = "expire")]
+    fn py_expire(&mut self) -> usize {
+        self.expire()
+    }
+
+    #[getter(pending_transfers)]
+    fn py_pending_transfers(&self) -> usize {
+        self.pending_transfers()
+    }
+
+    #[getter(pending_bytes)]
+    fn py_pending_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Reassembler {{ pending_transfers: {}, pending_bytes: {} }}",
+            self.transfers.len(),
+            self.pending_bytes
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ProtocolError;
+    use crate::fragment::{
+        Fragment, Fragmenter, Reassembler, FRAGMENT_HEADER_SIZE, FRAGMENT_OVERHEAD,
+        MAX_FRAGMENT_COUNT,
+    };
+    use std::time::{Duration, Instant};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let message: Vec<u8> = (0..250u8).collect();
+        let fragmenter = Fragmenter::new(FRAGMENT_HEADER_SIZE + 100).unwrap();
+        let fragments = fragmenter.split(&message).unwrap();
+        assert_eq!(fragments.len(), 3);
+        assert!(fragments
+            .iter()
+            .all(|f| f.len() <= fragmenter.max_fragment_size()));
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(10), 1000);
+        assert_eq!(reassembler.push(&fragments[2]), Ok(None));
+        assert_eq!(reassembler.push(&fragments[0]), Ok(None));
+        assert_eq!(reassembler.push(&fragments[0]), Ok(None));
+        assert_eq!(reassembler.pending_bytes(), 150 + 2 * FRAGMENT_OVERHEAD);
+        assert_eq!(reassembler.push(&fragments[1]), Ok(Some(message)));
+        assert_eq!(reassembler.pending_transfers(), 0);
+        assert_eq!(reassembler.pending_bytes(), 0);
+
+        assert_eq!(fragmenter.split(&[1, 2]).unwrap(), [[1, 2]]);
+        assert_eq!(reassembler.push(&[1, 2]), Ok(Some(vec![1, 2])));
+        assert!(Fragmenter::new(FRAGMENT_HEADER_SIZE).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let fragmenter = Fragmenter::new(FRAGMENT_HEADER_SIZE + 100).unwrap();
+        let first = fragmenter.split(&[1; 200]).unwrap();
+        let second = fragmenter.split(&[2; 200]).unwrap();
+        let start = Instant::now();
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1), 300);
+        reassembler.push_at(&first[0], start).unwrap();
+        reassembler
+            .push_at(&second[0], start + Duration::from_millis(1))
+            .unwrap();
+        assert_eq!(reassembler.pending_transfers(), 2);
+        assert_eq!(
+            reassembler.push_at(&second[1], start),
+            Ok(Some(vec![2; 200]))
+        );
+        assert_eq!(reassembler.pending_transfers(), 0);
+        assert_eq!(reassembler.push_at(&first[1], start), Ok(None));
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1), 1000);
+        reassembler.push_at(&first[0], start).unwrap();
+        assert_eq!(reassembler.expire_at(start + Duration::from_secs(2)), 1);
+        assert_eq!(reassembler.pending_bytes(), 0);
+
+        let mut reassembler = Reassembler::new(Duration::from_secs(1), 50);
+        assert!(matches!(
+            reassembler.push(&first[0]),
+            Err(ProtocolError::Fragment(_))
+        ));
+        assert!(matches!(
+            reassembler.push(&first[0][..10]),
+            Err(ProtocolError::Fragment(_))
+        ));
+    }
+
+    #[test]
+    fn test_fragment_flood() {
+        let fragment = |count: u32, chunk: &[u8]| {
+            Fragment {
+                transfer_id: Uuid::new_v4(),
+                index: 0,
+                count,
+                chunk,
+            }
+            .write()
+        };
+        let mut reassembler = Reassembler::new(Duration::from_secs(10), 1000);
+        for _ in 0..10_000 {
+            assert!(matches!(
+                reassembler.push(&fragment(2, &[])),
+                Err(ProtocolError::Fragment(_))
+            ));
+        }
+        assert_eq!(reassembler.pending_transfers(), 0);
+        assert_eq!(reassembler.pending_bytes(), 0);
+
+        for _ in 0..10_000 {
+            assert_eq!(reassembler.push(&fragment(2, &[1])), Ok(None));
+            assert!(reassembler.pending_bytes() <= 1000);
+        }
+        assert_eq!(
+            reassembler.pending_transfers(),
+            1000 / (1 + FRAGMENT_OVERHEAD)
+        );
+
+        assert!(matches!(
+            reassembler.push(&fragment(MAX_FRAGMENT_COUNT + 1, &[1])),
+            Err(ProtocolError::Fragment(_))
+        ));
+        let fragmenter = Fragmenter::new(FRAGMENT_HEADER_SIZE + 1).unwrap();
+        assert!(fragmenter
+            .split(&vec![0; MAX_FRAGMENT_COUNT as usize + 1])
+            .is_err());
+    }
+}
@@ -0,0 +1,449 @@
+use crate::avro::{BuilderImpl, ProtocolMessage};
+use crate::error::{ProtocolError, Result};
+use std::io::{self, Read, Write};
+
+/// Longest unsigned LEB128 encoding of a `u64`.
+const MAX_VARINT_SIZE: usize = 10;
+
+/// How the length of every frame is written ahead of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthPrefix {
+    /// Unsigned LEB128, the protobuf varint.
+    #[default]
+    Varint,
+    /// Four bytes, big-endian.
+    U32,
+}
+
+/// Framing of the envelopes on a byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameFormat {
+    pub prefix: LengthPrefix,
+    /// Frames longer than this are rejected by both sides.
+    pub max_frame_size: usize,
+}
+
+impl FrameFormat {
+    pub fn new(prefix: LengthPrefix, max_frame_size: usize) -> Self {
+        FrameFormat {
+            prefix,
+            max_frame_size,
+        }
+    }
+
+    fn encode_prefix(&self, size: usize) -> Result<Vec<u8>> {
+        self.check_size(size as u64)?;
+        match self.prefix {
+            LengthPrefix::Varint => {
+                let mut buf = Vec::with_capacity(MAX_VARINT_SIZE);
+                let mut size = size as u64;
+                while size >= 0x80 {
+                    buf.push(size as u8 | 0x80);
+                    size >>= 7;
+                }
+                buf.push(size as u8);
+                Ok(buf)
+            }
+            LengthPrefix::U32 => u32::try_from(size)
+                .map(|size| size.to_be_bytes().to_vec())
+                .map_err(|_| frame_too_large(size as u64, self.max_frame_size)),
+        }
+    }
+
+    fn check_size(&self, size: u64) -> Result<usize> {
+        match usize::try_from(size) {
+            Ok(size) if size <= self.max_frame_size => Ok(size),
+            _ => Err(frame_too_large(size, self.max_frame_size)),
+        }
+    }
+}
+
+impl Default for FrameFormat {
+    /// Varint prefix and frames of 16 MiB at most.
+    fn default() -> Self {
+        FrameFormat::new(LengthPrefix::Varint, 16 << 20)
+    }
+}
+
+fn frame_too_large(size: u64, max: usize) -> ProtocolError {
+    ProtocolError::FrameTooLarge { size, max }
+}
+
+fn stream_error(e: io::Error) -> ProtocolError {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => ProtocolError::Stream(String::from("Truncated frame")),
+        _ => ProtocolError::Stream(e.to_string()),
+    }
+}
+
+/// Decodes the prefix byte by byte; `None` if the stream ends before the first byte.
+struct PrefixDecoder {
+    format: FrameFormat,
+    buf: [u8; MAX_VARINT_SIZE],
+    len: usize,
+    /// The varint decoded so far.
+    value: u64,
+}
+
+impl PrefixDecoder {
+    fn new(format: FrameFormat) -> Self {
+        PrefixDecoder {
+            format,
+            buf: [0; MAX_VARINT_SIZE],
+            len: 0,
+            value: 0,
+        }
+    }
+
+    /// The frame size once the prefix is complete.
+    fn push(&mut self, byte: u8) -> Result<Option<usize>> {
+        self.buf[self.len] = byte;
+        self.len += 1;
+        match self.format.prefix {
+            LengthPrefix::Varint => {
+                let malformed =
+                    || ProtocolError::Stream(String::from("Malformed varint frame length"));
+                let bits = u64::from(byte & 0x7f);
+                // The tenth byte holds the last bit of a u64 and ends the varint.
+                if self.len == MAX_VARINT_SIZE && (bits > 1 || byte & 0x80 != 0) {
+                    return Err(malformed());
+                }
+                self.value |= bits << (7 * (self.len - 1));
+                if byte & 0x80 == 0 {
+                    return self.format.check_size(self.value).map(Some);
+                }
+                // The bytes to come only add to the size, so an oversized frame is rejected
+                // without waiting for the rest of its prefix.
+                self.format.check_size(self.value)?;
+                Ok(None)
+            }
+            LengthPrefix::U32 if self.len == 4 => {
+                let size = u32::from_be_bytes([self.buf[0], self.buf[1], self.buf[2], self.buf[3]]);
+                self.format.check_size(u64::from(size)).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Writes length-prefixed envelopes to a byte stream.
+pub struct FrameWriter<W: Write> {
+    inner: W,
+    format: FrameFormat,
+}
+
+impl<W: Write> FrameWriter<W> {
+    pub fn new(inner: W, format: FrameFormat) -> Self {
+        FrameWriter { inner, format }
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        let prefix = self.format.encode_prefix(frame.len())?;
+        self.inner.write_all(&prefix).map_err(stream_error)?;
+        self.inner.write_all(frame).map_err(stream_error)
+    }
+
+    /// Packs the message with the builder's envelope settings and writes it as one frame.
+    pub fn write_message(&mut self, builder: &BuilderImpl, message: ProtocolMessage) -> Result<()> {
+        self.write_frame(&builder.pack_message(message)?)
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush().map_err(stream_error)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+/// Reads length-prefixed envelopes from a byte stream.
+///
+/// The prefix is read byte by byte, so wrap unbuffered streams in a `BufReader`.
+pub struct FrameReader<R: Read> {
+    inner: R,
+    format: FrameFormat,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R, format: FrameFormat) -> Self {
+        FrameReader { inner, format }
+    }
+
+    /// The next frame, `None` once the stream ends between frames.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut prefix = PrefixDecoder::new(self.format);
+        let size = loop {
+            let byte = match self.read_byte()? {
+                Some(byte) => byte,
+                None if prefix.is_empty() => return Ok(None),
+                None => return Err(stream_error(io::ErrorKind::UnexpectedEof.into())),
+            };
+            if let Some(size) = prefix.push(byte)? {
+                break size;
+            }
+        };
+        let mut frame = vec![0; size];
+        self.inner.read_exact(&mut frame).map_err(stream_error)?;
+        Ok(Some(frame))
+    }
+
+    /// The next byte, `None` at the end of the stream; interrupted reads are retried.
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut byte = [0u8];
+        loop {
+            match self.inner.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(stream_error(e)),
+            }
+        }
+    }
+
+    /// The next message decoded by the builder, `None` once the stream ends between frames.
+    pub fn read_message(&mut self, builder: &BuilderImpl) -> Result<Option<ProtocolMessage>> {
+        self.read_frame()?
+            .map(|frame| builder.read_message(&frame))
+            .transpose()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio_io::{AsyncFrameReader, AsyncFrameWriter};
+
+#[cfg(feature = "tokio")]
+mod tokio_io {
+    use super::{stream_error, FrameFormat, PrefixDecoder};
+    use crate::avro::{BuilderImpl, ProtocolMessage};
+    use crate::error::Result;
+    use std::io;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    /// The `FrameWriter` over a tokio stream.
+    pub struct AsyncFrameWriter<W: AsyncWrite + Unpin> {
+        inner: W,
+        format: FrameFormat,
+    }
+
+    impl<W: AsyncWrite + Unpin> AsyncFrameWriter<W> {
+        pub fn new(inner: W, format: FrameFormat) -> Self {
+            AsyncFrameWriter { inner, format }
+        }
+
+        pub async fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+            let prefix = self.format.encode_prefix(frame.len())?;
+            self.inner.write_all(&prefix).await.map_err(stream_error)?;
+            self.inner.write_all(frame).await.map_err(stream_error)
+        }
+
+        pub async fn write_message(
+            &mut self,
+            builder: &BuilderImpl,
+            message: ProtocolMessage,
+        ) -> Result<()> {
+            let frame = builder.pack_message(message)?;
+            self.write_frame(&frame).await
+        }
+
+        pub async fn flush(&mut self) -> Result<()> {
+            self.inner.flush().await.map_err(stream_error)
+        }
+
+        pub fn get_ref(&self) -> &W {
+            &self.inner
+        }
+
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+
+    /// The `FrameReader` over a tokio stream.
+    pub struct AsyncFrameReader<R: AsyncRead + Unpin> {
+        inner: R,
+        format: FrameFormat,
+    }
+
+    impl<R: AsyncRead + Unpin> AsyncFrameReader<R> {
+        pub fn new(inner: R, format: FrameFormat) -> Self {
+            AsyncFrameReader { inner, format }
+        }
+
+        pub async fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
+            let mut prefix = PrefixDecoder::new(self.format);
+            let size = loop {
+                let mut byte = [0u8];
+                let read = match self.inner.read(&mut byte).await {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    read => read.map_err(stream_error)?,
+                };
+                if read == 0 {
+                    if prefix.is_empty() {
+                        return Ok(None);
+                    }
+                    return Err(stream_error(io::ErrorKind::UnexpectedEof.into()));
+                }
+                if let Some(size) = prefix.push(byte[0])? {
+                    break size;
+                }
+            };
+            let mut frame = vec![0; size];
+            self.inner
+                .read_exact(&mut frame)
+                .await
+                .map_err(stream_error)?;
+            Ok(Some(frame))
+        }
+
+        pub async fn read_message(
+            &mut self,
+            builder: &BuilderImpl,
+        ) -> Result<Option<ProtocolMessage>> {
+            match self.read_frame().await? {
+                Some(frame) => builder.read_message(&frame).map(Some),
+                None => Ok(None),
+            }
+        }
+
+        pub fn get_ref(&self) -> &R {
+            &self.inner
+        }
+
+        pub fn into_inner(self) -> R {
+            self.inner
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ProtocolError;
+    use crate::framing::{FrameFormat, FrameReader, FrameWriter, LengthPrefix};
+    use std::io::{self, Read};
+
+    #[test]
+    fn test_frames() {
+        for prefix in [LengthPrefix::Varint, LengthPrefix::U32] {
+            let format = FrameFormat::new(prefix, 1000);
+            let mut writer = FrameWriter::new(Vec::new(), format);
+            writer.write_frame(&[1, 2, 3]).unwrap();
+            writer.write_frame(&[]).unwrap();
+            writer.write_frame(&[7; 300]).unwrap();
+            assert!(matches!(
+                writer.write_frame(&[0; 1001]),
+                Err(ProtocolError::FrameTooLarge {
+                    size: 1001,
+                    max: 1000
+                })
+            ));
+            let bytes = writer.into_inner();
+
+            let mut reader = FrameReader::new(&bytes[..], format);
+            assert_eq!(reader.read_frame(), Ok(Some(vec![1, 2, 3])));
+            assert_eq!(reader.read_frame(), Ok(Some(vec![])));
+            assert_eq!(reader.read_frame(), Ok(Some(vec![7; 300])));
+            assert_eq!(reader.read_frame(), Ok(None));
+
+            let mut reader = FrameReader::new(&bytes[..bytes.len() - 1], format);
+            reader.read_frame().unwrap();
+            reader.read_frame().unwrap();
+            assert!(matches!(reader.read_frame(), Err(ProtocolError::Stream(_))));
+
+            let mut reader = FrameReader::new(&bytes[..], FrameFormat::new(prefix, 100));
+            reader.read_frame().unwrap();
+            reader.read_frame().unwrap();
+            assert!(matches!(
+                reader.read_frame(),
+                Err(ProtocolError::FrameTooLarge { size: 300, .. })
+            ));
+        }
+        let mut writer = FrameWriter::new(Vec::new(), FrameFormat::default());
+        writer.write_frame(&[0; 300]).unwrap();
+        assert_eq!(writer.get_ref()[..2], [0xAC, 0x02]);
+    }
+
+    #[test]
+    fn test_malformed_prefix() {
+        let format = FrameFormat::new(LengthPrefix::Varint, usize::MAX);
+        let overlong = [0xff; 11];
+        assert!(matches!(
+            FrameReader::new(&overlong[..], format).read_frame(),
+            Err(ProtocolError::Stream(_))
+        ));
+        let mut wrapping = [0x80; 10];
+        wrapping[9] = 0x02;
+        assert!(matches!(
+            FrameReader::new(&wrapping[..], format).read_frame(),
+            Err(ProtocolError::Stream(_))
+        ));
+        // Rejected as soon as the prefix exceeds the limit, whatever follows.
+        let format = FrameFormat::new(LengthPrefix::Varint, 1000);
+        assert!(matches!(
+            FrameReader::new(&[0xff, 0xff][..], format).read_frame(),
+            Err(ProtocolError::FrameTooLarge { .. })
+        ));
+    }
+
+    /// Fails every other read with `Interrupted`.
+    struct Interrupting<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl Read for Interrupting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    #[test]
+    fn test_interrupted_reads() {
+        let mut writer = FrameWriter::new(Vec::new(), FrameFormat::default());
+        writer.write_frame(&[7; 300]).unwrap();
+        let bytes = writer.into_inner();
+        let stream = Interrupting {
+            data: &bytes,
+            interrupt: false,
+        };
+        let mut reader = FrameReader::new(stream, FrameFormat::default());
+        assert_eq!(reader.read_frame(), Ok(Some(vec![7; 300])));
+        assert_eq!(reader.read_frame(), Ok(None));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_frames() {
+        use crate::framing::{AsyncFrameReader, AsyncFrameWriter};
+
+        let mut writer = AsyncFrameWriter::new(Vec::new(), FrameFormat::default());
+        writer.write_frame(&[1, 2, 3]).await.unwrap();
+        writer.write_frame(&[7; 300]).await.unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = AsyncFrameReader::new(&bytes[..], FrameFormat::default());
+        assert_eq!(reader.read_frame().await, Ok(Some(vec![1, 2, 3])));
+        assert_eq!(reader.read_frame().await, Ok(Some(vec![7; 300])));
+        assert_eq!(reader.read_frame().await, Ok(None));
+    }
+}
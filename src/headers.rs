@@ -0,0 +1,143 @@
+use crate::error::Result;
+use crate::record::RecordFields;
+use crate::utils::gen_hash_map;
+use avro_rs::types::Value;
+use avro_rs::Schema;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+pub const HEADERS_SCHEMA: &str = "insight.transport.Headers";
+
+/// Schema of the headers block preceding the envelope.
+pub const HEADERS_SCHEMA_JSON: &str = r#"{
+    "type": "record",
+    "name": "Headers",
+    "namespace": "insight.transport",
+    "fields": [
+        {"name": "message_id", "type": ["null", "string"], "default": null},
+        {"name": "module_id", "type": ["null", "string"], "default": null},
+        {"name": "timestamp_ms", "type": ["null", "long"], "default": null},
+        {"name": "correlation_id", "type": ["null", "string"], "default": null},
+        {"name": "causation_id", "type": ["null", "string"], "default": null},
+        {"name": "extra", "type": {"type": "map", "values": "string"}, "default": {}}
+    ]
+}"#;
+
+/// Optional metadata carried next to the message in the envelope.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+pub struct Headers {
+    /// Unique id of the message, a UUID unless the sender chooses otherwise.
+    pub message_id: Option<String>,
+    /// The module which sent the message.
+    pub module_id: Option<String>,
+    /// Creation time of the message in milliseconds since the Unix epoch.
+    pub timestamp_ms: Option<i64>,
+    /// Id shared by all the messages of one conversation, e.g. a request and its responses.
+    pub correlation_id: Option<String>,
+    /// `message_id` of the message this one is a reaction to.
+    pub causation_id: Option<String>,
+    pub extra: HashMap<String, String>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Headers with a fresh random message id and the current time.
+    pub fn stamped() -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        Headers {
+            message_id: Some(Uuid::new_v4().to_hyphenated().to_string()),
+            timestamp_ms: Some(timestamp_ms),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub(crate) fn to_avro(&self) -> Value {
+        let optional = |v: Option<Value>| Value::Union(Box::new(v.unwrap_or(Value::Null)));
+        let string = |s: &Option<String>| optional(s.clone().map(Value::String));
+        Value::Record(vec![
+            ("message_id".into(), string(&self.message_id)),
+            ("module_id".into(), string(&self.module_id)),
+            (
+                "timestamp_ms".into(),
+                optional(self.timestamp_ms.map(Value::Long)),
+            ),
+            ("correlation_id".into(), string(&self.correlation_id)),
+            ("causation_id".into(), string(&self.causation_id)),
+            ("extra".into(), gen_hash_map(&self.extra)),
+        ])
+    }
+
+    pub(crate) fn from_avro(value: &Value, schema: &Schema) -> Result<Headers> {
+        let fields = RecordFields::with_schema(HEADERS_SCHEMA, value, schema)?;
+        Ok(Headers {
+            message_id: fields.get_optional("message_id")?,
+            module_id: fields.get_optional("module_id")?,
+            timestamp_ms: fields.get_optional("timestamp_ms")?,
+            correlation_id: fields.get_optional("correlation_id")?,
+            causation_id: fields.get_optional("causation_id")?,
+            extra: fields.get_or_default("extra")?,
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Headers {
+    #[new]
+    #[pyo3(signature = (
+        message_id = None,
+        module_id = None,
+        timestamp_ms = None,
+        correlation_id = None,
+        causation_id = None,
+        extra = HashMap::default()
+    ))]
+    fn py_new(
+        message_id: Option<String>,
+        module_id: Option<String>,
+        timestamp_ms: Option<i64>,
+        correlation_id: Option<String>,
+        causation_id: Option<String>,
+        extra: HashMap<String, String>,
+    ) -> Self {
+        Headers {
+            message_id,
+            module_id,
+            timestamp_ms,
+            correlation_id,
+            causation_id,
+            extra,
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "stamped")]
+    fn py_stamped() -> Self {
+        Self::stamped()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
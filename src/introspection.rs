@@ -0,0 +1,235 @@
+use crate::avro::BuilderImpl;
+use avro_rs::schema::{Name, RecordField};
+use avro_rs::Schema;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use serde_json::{json, Value as JsonValue};
+
+/// A schema of the catalog as listed to the operators.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct SchemaInfo {
+    /// Avro full name the schema is registered under, e.g. `insight.storage.Unit`.
+    pub name: String,
+    /// Avro name of the type without its namespace.
+    pub type_name: String,
+    pub namespace: Option<String>,
+    /// Full names the schema is also looked up by.
+    pub aliases: Vec<String>,
+    /// Fingerprint of the current version of the schema.
+    pub fingerprint: u64,
+    /// `record`, `enum` or `fixed`.
+    pub kind: String,
+    pub doc: Option<String>,
+    /// Fields of a record, empty for an enum.
+    pub fields: Vec<FieldInfo>,
+    /// Symbols of an enum, empty otherwise.
+    pub symbols: Vec<String>,
+    /// Size in bytes of a fixed type, `None` otherwise.
+    pub size: Option<usize>,
+}
+
+impl SchemaInfo {
+    pub(crate) fn new(name: &str, schema: &Schema, fingerprint: u64) -> Self {
+        let mut size = None;
+        let (kind, type_name, doc, fields, symbols) = match schema {
+            Schema::Record {
+                name, doc, fields, ..
+            } => (
+                "record",
+                name,
+                doc.clone(),
+                fields.iter().map(FieldInfo::new).collect(),
+                Vec::new(),
+            ),
+            Schema::Enum { name, doc, symbols } => {
+                ("enum", name, doc.clone(), Vec::new(), symbols.clone())
+            }
+            Schema::Fixed { name, size: len } => {
+                size = Some(*len);
+                ("fixed", name, None, Vec::new(), Vec::new())
+            }
+            _ => unreachable!("Only named types are registered"),
+        };
+        SchemaInfo {
+            name: String::from(name),
+            type_name: type_name.name.clone(),
+            namespace: type_name.namespace.clone(),
+            aliases: BuilderImpl::schema_aliases(schema),
+            fingerprint,
+            kind: String::from(kind),
+            doc,
+            fields,
+            symbols,
+            size,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl SchemaInfo {
+    fn __repr__(&self) -> String {
+        format!(
+            "SchemaInfo {{ name: {}, fingerprint: {:016x}, kind: {}, fields: {} }}",
+            self.name,
+            self.fingerprint,
+            self.kind,
+            self.fields.len()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+/// A field of a record schema.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct FieldInfo {
+    pub name: String,
+    /// Type of the field in the Avro JSON notation; named types are referred to by full name.
+    pub schema: String,
+    /// Default value in the Avro JSON encoding, `None` if the field has none.
+    pub default: Option<String>,
+    pub doc: Option<String>,
+}
+
+impl FieldInfo {
+    fn new(field: &RecordField) -> Self {
+        FieldInfo {
+            name: field.name.clone(),
+            schema: type_json(&field.schema).to_string(),
+            default: field.default.as_ref().map(JsonValue::to_string),
+            doc: field.doc.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl FieldInfo {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+fn fullname(name: &Name) -> JsonValue {
+    JsonValue::from(name.fullname(None))
+}
+
+/// The type as written in a schema, without expanding the named types.
+fn type_json(schema: &Schema) -> JsonValue {
+    let logical = |base: &str, logical: &str| json!({"type": base, "logicalType": logical});
+    match schema {
+        Schema::Null => json!("null"),
+        Schema::Boolean => json!("boolean"),
+        Schema::Int => json!("int"),
+        Schema::Long => json!("long"),
+        Schema::Float => json!("float"),
+        Schema::Double => json!("double"),
+        Schema::Bytes => json!("bytes"),
+        Schema::String => json!("string"),
+        Schema::Array(items) => json!({"type": "array", "items": type_json(items)}),
+        Schema::Map(values) => json!({"type": "map", "values": type_json(values)}),
+        Schema::Union(union) => union.variants().iter().map(type_json).collect(),
+        Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } => {
+            fullname(name)
+        }
+        Schema::Decimal {
+            precision,
+            scale,
+            inner,
+        } => json!({
+            "type": type_json(inner),
+            "logicalType": "decimal",
+            "precision": precision,
+            "scale": scale
+        }),
+        Schema::Uuid => logical("string", "uuid"),
+        Schema::Date => logical("int", "date"),
+        Schema::TimeMillis => logical("int", "time-millis"),
+        Schema::TimeMicros => logical("long", "time-micros"),
+        Schema::TimestampMillis => logical("long", "timestamp-millis"),
+        Schema::TimestampMicros => logical("long", "timestamp-micros"),
+        Schema::Duration => {
+            json!({"type": "fixed", "name": "duration", "size": 12, "logicalType": "duration"})
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::BuilderImpl;
+    use crate::error::ProtocolError;
+    use crate::introspection::FieldInfo;
+    use serde_json::json;
+
+    const READING_SCHEMA_JSON: &str = r#"{
+        "type": "record",
+        "name": "Reading",
+        "namespace": "insight.custom",
+        "doc": "A sensor reading",
+        "fields": [
+            {"name": "track_type", "type": "insight.storage.TrackType"},
+            {"name": "label", "type": ["null", "string"], "default": null, "doc": "Shown in the UI"},
+            {"name": "values", "type": {"type": "array", "items": "double"}}
+        ]
+    }"#;
+
+    #[test]
+    fn test_schema_info() {
+        let mut mb = BuilderImpl::default();
+        let name = mb.register_schema(READING_SCHEMA_JSON).unwrap();
+        let info = mb.schema_info(&name).unwrap();
+        assert_eq!(info.type_name, "Reading");
+        assert_eq!(info.namespace.as_deref(), Some("insight.custom"));
+        assert_eq!(info.fingerprint, mb.fingerprint(&name).unwrap());
+        assert_eq!(info.kind, "record");
+        assert_eq!(info.doc.as_deref(), Some("A sensor reading"));
+        assert_eq!(
+            info.fields[..2],
+            [
+                FieldInfo {
+                    name: String::from("track_type"),
+                    schema: String::from(r#""insight.storage.TrackType""#),
+                    default: None,
+                    doc: None
+                },
+                FieldInfo {
+                    name: String::from("label"),
+                    schema: String::from(r#"["null","string"]"#),
+                    default: Some(String::from("null")),
+                    doc: Some(String::from("Shown in the UI"))
+                }
+            ]
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&info.fields[2].schema).unwrap(),
+            json!({"type": "array", "items": "double"})
+        );
+
+        assert!(mb.schemas().unwrap().iter().any(|s| s.name == name));
+        assert!(mb
+            .schema_json(&name)
+            .unwrap()
+            .starts_with(r#"{"name":"insight.custom.Reading","type":"record""#));
+        assert_eq!(
+            mb.schema_json("insight.custom.Missing"),
+            Err(ProtocolError::UnknownSchema(String::from(
+                "insight.custom.Missing"
+            )))
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use crate::avro::{
+    STREAM_TRACKS_REQUEST_SCHEMA, STREAM_TRACKS_RESPONSE_SCHEMA, TRACK_INFO_SCHEMA, UNIT_SCHEMA,
+};
+use crate::error::{ProtocolError, Result};
+use crate::primitives::pack_track_name;
+use avro_rs::schema::{Name, Schema};
+use avro_rs::types::Value;
+use serde_json::{Map, Number, Value as Json};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// How a `bytes` or `fixed` field is rendered in JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BytesEncoding {
+    /// One character per byte, code points 0-255, as the Avro JSON encoding defines.
+    Avro,
+    /// A `StreamName`, rendered as the UUID string.
+    Uuid,
+    /// A zero-padded `TrackName`, rendered as the text without the padding, which is restored
+    /// on parsing.
+    Text,
+}
+
+/// Only the fields of the built-in records holding a `StreamName` or a `TrackName` are rendered
+/// as such, the `bytes` fields of the custom schemas keep the Avro encoding whatever their name.
+fn field_encoding(record: &Name, field: &str) -> BytesEncoding {
+    match (record.fullname(None).as_str(), field) {
+        (
+            UNIT_SCHEMA | STREAM_TRACKS_REQUEST_SCHEMA | STREAM_TRACKS_RESPONSE_SCHEMA,
+            "stream_name",
+        ) => BytesEncoding::Uuid,
+        (UNIT_SCHEMA, "track_name") | (TRACK_INFO_SCHEMA, "name") => BytesEncoding::Text,
+        _ => BytesEncoding::Avro,
+    }
+}
+
+/// Renders the Avro value of the schema in the Avro JSON encoding.
+pub fn value_to_json(value: &Value, schema: &Schema) -> Result<Json> {
+    to_json(value, schema, BytesEncoding::Avro, "").map_err(ProtocolError::Encode)
+}
+
+/// Parses the Avro JSON encoding of a value of the schema.
+pub fn json_to_value(json: &Json, schema: &Schema) -> Result<Value> {
+    from_json(json, schema, BytesEncoding::Avro, "").map_err(|reason| {
+        ProtocolError::PayloadDecode {
+            schema: schema_name(schema),
+            reason,
+        }
+    })
+}
+
+fn schema_name(schema: &Schema) -> String {
+    match schema {
+        Schema::Record { name, .. } | Schema::Enum { name, .. } | Schema::Fixed { name, .. } => {
+            name.fullname(None)
+        }
+        Schema::Null => String::from("null"),
+        Schema::Boolean => String::from("boolean"),
+        Schema::Int | Schema::Date | Schema::TimeMillis => String::from("int"),
+        Schema::Long | Schema::TimeMicros | Schema::TimestampMillis | Schema::TimestampMicros => {
+            String::from("long")
+        }
+        Schema::Float => String::from("float"),
+        Schema::Double => String::from("double"),
+        Schema::Bytes | Schema::Decimal { .. } => String::from("bytes"),
+        Schema::String | Schema::Uuid => String::from("string"),
+        Schema::Array(_) => String::from("array"),
+        Schema::Map(_) => String::from("map"),
+        Schema::Union(_) => String::from("union"),
+        Schema::Duration => String::from("fixed"),
+    }
+}
+
+fn field_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        String::from(name)
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn mismatch<T>(
+    path: &str,
+    found: impl std::fmt::Debug,
+    schema: &Schema,
+) -> std::result::Result<T, String> {
+    Err(format!(
+        "Value {:?} of the field ({}) does not match the schema ({})",
+        found,
+        path,
+        schema_name(schema)
+    ))
+}
+
+fn bytes_to_json(
+    bytes: &[u8],
+    encoding: BytesEncoding,
+    path: &str,
+) -> std::result::Result<Json, String> {
+    Ok(Json::String(match encoding {
+        BytesEncoding::Avro => bytes.iter().map(|b| char::from(*b)).collect(),
+        BytesEncoding::Uuid => Uuid::from_slice(bytes)
+            .map_err(|e| format!("{} ({})", e, path))?
+            .to_hyphenated()
+            .to_string(),
+        BytesEncoding::Text => {
+            let len = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+            String::from_utf8(bytes[..len].to_vec()).map_err(|e| format!("{} ({})", e, path))?
+        }
+    }))
+}
+
+fn json_to_bytes(
+    s: &str,
+    encoding: BytesEncoding,
+    path: &str,
+) -> std::result::Result<Vec<u8>, String> {
+    match encoding {
+        BytesEncoding::Avro => s
+            .chars()
+            .map(|c| {
+                u8::try_from(u32::from(c))
+                    .map_err(|_| format!("Character {:?} is not a byte ({})", c, path))
+            })
+            .collect(),
+        BytesEncoding::Uuid => Uuid::parse_str(s)
+            .map(|uuid| uuid.as_bytes().to_vec())
+            .map_err(|e| format!("{} ({})", e, path)),
+        BytesEncoding::Text => pack_track_name(s)
+            .map(|track_name| track_name.to_vec())
+            .map_err(|e| format!("{} ({})", e, path)),
+    }
+}
+
+fn to_json(
+    value: &Value,
+    schema: &Schema,
+    encoding: BytesEncoding,
+    path: &str,
+) -> std::result::Result<Json, String> {
+    Ok(match (value, schema) {
+        (Value::Null, Schema::Null) => Json::Null,
+        (Value::Boolean(b), Schema::Boolean) => Json::Bool(*b),
+        (Value::Int(i), Schema::Int)
+        | (Value::Date(i), Schema::Date)
+        | (Value::TimeMillis(i), Schema::TimeMillis) => Json::from(*i),
+        (Value::Long(l), Schema::Long)
+        | (Value::TimeMicros(l), Schema::TimeMicros)
+        | (Value::TimestampMillis(l), Schema::TimestampMillis)
+        | (Value::TimestampMicros(l), Schema::TimestampMicros) => Json::from(*l),
+        (Value::Float(f), Schema::Float) => float_to_json(f64::from(*f), path)?,
+        (Value::Double(d), Schema::Double) => float_to_json(*d, path)?,
+        (Value::Bytes(b), Schema::Bytes) | (Value::Fixed(_, b), Schema::Fixed { .. }) => {
+            bytes_to_json(b, encoding, path)?
+        }
+        (Value::String(s), Schema::String) | (Value::Enum(_, s), Schema::Enum { .. }) => {
+            Json::String(s.clone())
+        }
+        (Value::Array(items), Schema::Array(item_schema)) => Json::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_json(item, item_schema, encoding, &format!("{}[{}]", path, i)))
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+        (Value::Map(values), Schema::Map(value_schema)) => Json::Object(
+            values
+                .iter()
+                .map(|(k, v)| {
+                    Ok((
+                        k.clone(),
+                        to_json(v, value_schema, encoding, &field_path(path, k))?,
+                    ))
+                })
+                .collect::<std::result::Result<Map<_, _>, String>>()?,
+        ),
+        (value, Schema::Union(union)) => {
+            let inner = match value {
+                Value::Union(inner) => inner.as_ref(),
+                value => value,
+            };
+            if *inner == Value::Null {
+                return Ok(Json::Null);
+            }
+            let variant = union
+                .variants()
+                .iter()
+                .find(|variant| !matches!(variant, Schema::Null) && inner.validate(variant));
+            match variant {
+                Some(variant) => {
+                    let mut wrapped = Map::new();
+                    wrapped.insert(
+                        schema_name(variant),
+                        to_json(inner, variant, encoding, path)?,
+                    );
+                    Json::Object(wrapped)
+                }
+                None => return mismatch(path, inner, schema),
+            }
+        }
+        (
+            Value::Record(fields),
+            Schema::Record {
+                name,
+                fields: field_schemas,
+                ..
+            },
+        ) => {
+            let values: HashMap<&str, &Value> =
+                fields.iter().map(|(k, v)| (k.as_str(), v)).collect();
+            Json::Object(
+                field_schemas
+                    .iter()
+                    .map(|field| {
+                        let path = field_path(path, &field.name);
+                        let value = values
+                            .get(field.name.as_str())
+                            .ok_or_else(|| format!("Missing field ({})", path))?;
+                        let encoding = field_encoding(name, &field.name);
+                        Ok((
+                            field.name.clone(),
+                            to_json(value, &field.schema, encoding, &path)?,
+                        ))
+                    })
+                    .collect::<std::result::Result<Map<_, _>, String>>()?,
+            )
+        }
+        _ => return mismatch(path, value, schema),
+    })
+}
+
+fn float_to_json(f: f64, path: &str) -> std::result::Result<Json, String> {
+    Number::from_f64(f).map(Json::Number).ok_or_else(|| {
+        format!(
+            "Value {} of the field ({}) has no JSON representation",
+            f, path
+        )
+    })
+}
+
+fn from_json(
+    json: &Json,
+    schema: &Schema,
+    encoding: BytesEncoding,
+    path: &str,
+) -> std::result::Result<Value, String> {
+    Ok(match (json, schema) {
+        (Json::Null, Schema::Null) => Value::Null,
+        (Json::Bool(b), Schema::Boolean) => Value::Boolean(*b),
+        (Json::Number(n), Schema::Int | Schema::Date | Schema::TimeMillis) => {
+            let i = n
+                .as_i64()
+                .and_then(|i| i32::try_from(i).ok())
+                .ok_or_else(|| format!("Value {} of the field ({}) is not an int", n, path))?;
+            match schema {
+                Schema::Date => Value::Date(i),
+                Schema::TimeMillis => Value::TimeMillis(i),
+                _ => Value::Int(i),
+            }
+        }
+        (
+            Json::Number(n),
+            Schema::Long | Schema::TimeMicros | Schema::TimestampMillis | Schema::TimestampMicros,
+        ) => {
+            let l = n
+                .as_i64()
+                .ok_or_else(|| format!("Value {} of the field ({}) is not a long", n, path))?;
+            match schema {
+                Schema::TimeMicros => Value::TimeMicros(l),
+                Schema::TimestampMillis => Value::TimestampMillis(l),
+                Schema::TimestampMicros => Value::TimestampMicros(l),
+                _ => Value::Long(l),
+            }
+        }
+        (Json::Number(n), Schema::Float) => Value::Float(n.as_f64().unwrap_or_default() as f32),
+        (Json::Number(n), Schema::Double) => Value::Double(n.as_f64().unwrap_or_default()),
+        (Json::String(s), Schema::Bytes) => Value::Bytes(json_to_bytes(s, encoding, path)?),
+        (Json::String(s), Schema::Fixed { size, .. }) => {
+            let mut bytes = json_to_bytes(s, encoding, path)?;
+            if encoding == BytesEncoding::Text && bytes.len() <= *size {
+                bytes.resize(*size, 0);
+            }
+            if bytes.len() != *size {
+                return Err(format!(
+                    "Fixed value of the field ({}) must be {} bytes long, got {}",
+                    path,
+                    size,
+                    bytes.len()
+                ));
+            }
+            Value::Fixed(*size, bytes)
+        }
+        (Json::String(s), Schema::String) => Value::String(s.clone()),
+        (Json::String(s), Schema::Enum { symbols, .. }) => {
+            match symbols.iter().position(|symbol| symbol == s) {
+                Some(index) => Value::Enum(index as i32, s.clone()),
+                None => return Err(format!("Unknown enum symbol {} of the field ({})", s, path)),
+            }
+        }
+        (Json::Array(items), Schema::Array(item_schema)) => Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    from_json(item, item_schema, encoding, &format!("{}[{}]", path, i))
+                })
+                .collect::<std::result::Result<_, _>>()?,
+        ),
+        (Json::Object(values), Schema::Map(value_schema)) => Value::Map(
+            values
+                .iter()
+                .map(|(k, v)| {
+                    Ok((
+                        k.clone(),
+                        from_json(v, value_schema, encoding, &field_path(path, k))?,
+                    ))
+                })
+                .collect::<std::result::Result<HashMap<_, _>, String>>()?,
+        ),
+        (Json::Null, Schema::Union(union))
+            if union.variants().iter().any(|v| matches!(v, Schema::Null)) =>
+        {
+            Value::Union(Box::new(Value::Null))
+        }
+        (Json::Object(wrapped), Schema::Union(union)) if wrapped.len() == 1 => {
+            let (type_name, inner) = wrapped.iter().next().unwrap();
+            match union
+                .variants()
+                .iter()
+                .find(|variant| schema_name(variant) == *type_name)
+            {
+                Some(variant) => Value::Union(Box::new(from_json(inner, variant, encoding, path)?)),
+                None => {
+                    return Err(format!(
+                        "Type {} of the field ({}) is not a variant of the union",
+                        type_name, path
+                    ))
+                }
+            }
+        }
+        (Json::Object(values), Schema::Record { name, fields, .. }) => Value::Record(
+            fields
+                .iter()
+                .map(|field| {
+                    let path = field_path(path, &field.name);
+                    let value = match (values.get(&field.name), &field.default) {
+                        (Some(value), _) => from_json(
+                            value,
+                            &field.schema,
+                            field_encoding(name, &field.name),
+                            &path,
+                        )?,
+                        (None, Some(default)) => Value::from(default.clone())
+                            .resolve(&field.schema)
+                            .map_err(|e| format!("{} ({})", e, path))?,
+                        (None, None) => return Err(format!("Missing field ({})", path)),
+                    };
+                    Ok((field.name.clone(), value))
+                })
+                .collect::<std::result::Result<_, String>>()?,
+        ),
+        _ => return mismatch(path, json, schema),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{bytes_to_json, field_encoding, json_to_bytes, BytesEncoding};
+    use crate::primitives::pack_track_name;
+    use avro_rs::schema::Name;
+    use serde_json::json;
+
+    fn name(namespace: &str, name: &str) -> Name {
+        Name {
+            name: String::from(name),
+            namespace: Some(String::from(namespace)),
+            aliases: None,
+        }
+    }
+
+    #[test]
+    fn test_field_encodings() {
+        let unit = name("insight.storage", "Unit");
+        assert_eq!(field_encoding(&unit, "stream_name"), BytesEncoding::Uuid);
+        assert_eq!(field_encoding(&unit, "track_name"), BytesEncoding::Text);
+        let track_info = name("insight.storage", "TrackInfo");
+        assert_eq!(field_encoding(&track_info, "name"), BytesEncoding::Text);
+
+        let custom = name("insight.custom", "Unit");
+        for field in ["stream_name", "track_name", "name"] {
+            assert_eq!(field_encoding(&custom, field), BytesEncoding::Avro);
+        }
+    }
+
+    #[test]
+    fn test_bytes_encodings() {
+        let bytes = [0u8, 0x41, 0xff];
+        let json = bytes_to_json(&bytes, BytesEncoding::Avro, "").unwrap();
+        assert_eq!(json, json!("\u{0}A\u{ff}"));
+        assert_eq!(
+            json_to_bytes("\u{0}A\u{ff}", BytesEncoding::Avro, ""),
+            Ok(bytes.to_vec())
+        );
+        assert!(json_to_bytes("\u{100}", BytesEncoding::Avro, "").is_err());
+
+        let uuid = uuid::Uuid::new_v4();
+        let json = bytes_to_json(uuid.as_bytes(), BytesEncoding::Uuid, "").unwrap();
+        assert_eq!(json, json!(uuid.to_hyphenated().to_string()));
+        assert_eq!(
+            json_to_bytes(json.as_str().unwrap(), BytesEncoding::Uuid, ""),
+            Ok(uuid.as_bytes().to_vec())
+        );
+
+        let track_name = pack_track_name("video").unwrap();
+        let json = bytes_to_json(&track_name, BytesEncoding::Text, "").unwrap();
+        assert_eq!(json, json!("video"));
+        assert_eq!(
+            json_to_bytes("video", BytesEncoding::Text, ""),
+            Ok(track_name.to_vec())
+        );
+        assert!(json_to_bytes(&"v".repeat(17), BytesEncoding::Text, "").is_err());
+    }
+}
@@ -1,29 +1,80 @@
-use crate::avro::Builder;
-use crate::objects::services::keep_alive::KeepAliveMessage;
-use crate::primitives::{NotifyType, Payload, TrackInfo, TrackType, Unit};
-use objects::services::ffprobe::{
-    ServicesFFProbeRequest, ServicesFFProbeResponse, ServicesFFProbeResponseType,
-};
-use objects::services::ping::{PingRequestResponse, PingRequestResponseType};
-use objects::services::storage::notify_message::NotifyMessage;
-use objects::services::storage::stream_track_unit_elements::{
-    StreamTrackUnitElementsRequest, StreamTrackUnitElementsResponse,
-};
-use objects::services::storage::stream_track_units::{
-    StreamTrackUnitsRequest, StreamTrackUnitsResponse,
-};
-use objects::services::storage::stream_tracks::{StreamTracksRequest, StreamTracksResponse};
-use objects::services::storage::unit_element_message::UnitElementMessage;
-use pyo3::prelude::*;
-
 pub mod avro;
+pub mod codec;
+pub mod compression;
+pub mod discovery;
+pub mod encryption;
+pub mod envelope;
+pub mod error;
+pub mod fingerprint;
+pub mod fragment;
+pub mod framing;
+pub mod headers;
+pub mod introspection;
+pub mod json;
+pub mod limits;
 pub mod objects;
 pub mod primitives;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod record;
+pub mod registry;
+pub mod replay;
+pub mod schemas;
+pub mod signing;
 pub mod utils;
 
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+#[cfg(feature = "python")]
 #[pymodule]
-fn protocol(_py: Python, m: &PyModule) -> PyResult<()> {
+fn protocol(py: Python, m: &PyModule) -> PyResult<()> {
+    use crate::avro::{Builder, ProtocolMessage};
+    use crate::compression::Compression;
+    use crate::discovery::{SchemaLoadFailure, SchemaScan};
+    use crate::envelope::EnvelopeFormat;
+    use crate::error::register_exceptions;
+    use crate::fragment::{Fragmenter, Reassembler};
+    use crate::headers::Headers;
+    use crate::introspection::{FieldInfo, SchemaInfo};
+    use crate::limits::DecodeLimits;
+    use crate::primitives::{NotifyType, Payload, TrackInfo, TrackType, Unit};
+    use crate::replay::{PyPlayer, PyRecorder, RecordedMessage};
+    use crate::signing::{Signature, SignatureStatus, VerifyPolicy};
+    use objects::services::ffprobe::{
+        ServicesFFProbeRequest, ServicesFFProbeResponse, ServicesFFProbeResponseType,
+    };
+    use objects::services::keep_alive::KeepAliveMessage;
+    use objects::services::ping::{PingRequestResponse, PingRequestResponseType};
+    use objects::services::storage::notify_message::NotifyMessage;
+    use objects::services::storage::stream_track_unit_elements::{
+        StreamTrackUnitElementsRequest, StreamTrackUnitElementsResponse,
+    };
+    use objects::services::storage::stream_track_units::{
+        StreamTrackUnitsRequest, StreamTrackUnitsResponse,
+    };
+    use objects::services::storage::stream_tracks::{StreamTracksRequest, StreamTracksResponse};
+    use objects::services::storage::unit_element_message::UnitElementMessage;
+
+    register_exceptions(py, m)?;
     m.add_class::<Builder>()?;
+    m.add_class::<EnvelopeFormat>()?;
+    m.add_class::<Compression>()?;
+    m.add_class::<Headers>()?;
+    m.add_class::<DecodeLimits>()?;
+    m.add_class::<SchemaInfo>()?;
+    m.add_class::<FieldInfo>()?;
+    m.add_class::<SchemaScan>()?;
+    m.add_class::<SchemaLoadFailure>()?;
+    m.add_class::<Fragmenter>()?;
+    m.add_class::<Reassembler>()?;
+    m.add_class::<PyRecorder>()?;
+    m.add_class::<PyPlayer>()?;
+    m.add_class::<RecordedMessage>()?;
+    m.add_class::<Signature>()?;
+    m.add_class::<SignatureStatus>()?;
+    m.add_class::<VerifyPolicy>()?;
+    m.add_class::<ProtocolMessage>()?;
     m.add_class::<UnitElementMessage>()?;
     m.add_class::<NotifyMessage>()?;
     m.add_class::<PingRequestResponse>()?;
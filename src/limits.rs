@@ -0,0 +1,286 @@
+use crate::error::{ProtocolError, Result};
+use avro_rs::Schema;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Bounds on what the decoder accepts from untrusted input.
+///
+/// Every datum is scanned against its schema before `from_avro_datum` touches it, so a crafted
+/// length or item count is rejected before the decoder allocates for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
+pub struct DecodeLimits {
+    /// Size of the received buffer and of the decompressed message.
+    pub max_message_size: usize,
+    /// Length of a single `bytes` or `string` value, the payload of the envelope included.
+    pub max_bytes_length: usize,
+    /// Items of a single array or map.
+    pub max_collection_items: usize,
+    /// Nesting of records, arrays and maps.
+    pub max_depth: usize,
+}
+
+impl DecodeLimits {
+    pub fn new(
+        max_message_size: usize,
+        max_bytes_length: usize,
+        max_collection_items: usize,
+        max_depth: usize,
+    ) -> Self {
+        DecodeLimits {
+            max_message_size,
+            max_bytes_length,
+            max_collection_items,
+            max_depth,
+        }
+    }
+
+    /// No limits beyond the available memory.
+    pub fn unlimited() -> Self {
+        Self::new(usize::MAX, usize::MAX, usize::MAX, usize::MAX)
+    }
+
+    pub(crate) fn check_message_size(&self, size: usize) -> Result<()> {
+        exceeded("message size", size as u64, self.max_message_size)
+    }
+
+    /// Scans the datum against the schema and fails on the first value over the limits.
+    ///
+    /// A malformed datum is left to the decoder to report; the scan stops where it ends.
+    pub(crate) fn check_datum(&self, schema: &Schema, datum: &[u8]) -> Result<()> {
+        let mut scanner = Scanner {
+            limits: self,
+            data: datum,
+        };
+        match scanner.skip(schema, 0) {
+            Err(Scan::Limit(e)) => Err(e),
+            Ok(()) | Err(Scan::Malformed) => Ok(()),
+        }
+    }
+}
+
+impl Default for DecodeLimits {
+    /// 64 MiB messages, 16 MiB values, a million items and 64 levels of nesting.
+    fn default() -> Self {
+        Self::new(64 << 20, 16 << 20, 1_000_000, 64)
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl DecodeLimits {
+    #[new]
+    #[pyo3(signature = (
+        max_message_size = None,
+        max_bytes_length = None,
+        max_collection_items = None,
+        max_depth = None
+    ))]
+    fn py_new(
+        max_message_size: Option<usize>,
+        max_bytes_length: Option<usize>,
+        max_collection_items: Option<usize>,
+        max_depth: Option<usize>,
+    ) -> Self {
+        let default = Self::default();
+        Self::new(
+            max_message_size.unwrap_or(default.max_message_size),
+            max_bytes_length.unwrap_or(default.max_bytes_length),
+            max_collection_items.unwrap_or(default.max_collection_items),
+            max_depth.unwrap_or(default.max_depth),
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+fn exceeded(limit: &str, value: u64, max: usize) -> Result<()> {
+    if value > max as u64 {
+        return Err(ProtocolError::LimitExceeded {
+            limit: String::from(limit),
+            value,
+            max: max as u64,
+        });
+    }
+    Ok(())
+}
+
+enum Scan {
+    Limit(ProtocolError),
+    Malformed,
+}
+
+impl From<ProtocolError> for Scan {
+    fn from(e: ProtocolError) -> Self {
+        Scan::Limit(e)
+    }
+}
+
+/// Walks the Avro binary encoding without materializing the values.
+struct Scanner<'a> {
+    limits: &'a DecodeLimits,
+    data: &'a [u8],
+}
+
+impl<'a> Scanner<'a> {
+    fn skip(&mut self, schema: &Schema, depth: usize) -> std::result::Result<(), Scan> {
+        match schema {
+            Schema::Null => Ok(()),
+            Schema::Boolean => self.advance(1),
+            Schema::Int
+            | Schema::Long
+            | Schema::Date
+            | Schema::TimeMillis
+            | Schema::TimeMicros
+            | Schema::TimestampMillis
+            | Schema::TimestampMicros
+            | Schema::Enum { .. } => self.read_long().map(drop),
+            Schema::Float => self.advance(4),
+            Schema::Double => self.advance(8),
+            Schema::Bytes | Schema::String | Schema::Uuid => self.skip_bytes(),
+            // A decimal is encoded as its underlying bytes or fixed.
+            Schema::Decimal { inner, .. } => self.skip(inner, depth),
+            Schema::Fixed { size, .. } => self.advance(*size),
+            Schema::Duration => self.advance(12),
+            Schema::Union(union) => {
+                let index = self.read_long()?;
+                let variant = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| union.variants().get(i))
+                    .ok_or(Scan::Malformed)?;
+                self.skip(variant, depth)
+            }
+            Schema::Record { fields, .. } => {
+                exceeded("nesting depth", depth as u64 + 1, self.limits.max_depth)?;
+                fields
+                    .iter()
+                    .try_for_each(|field| self.skip(&field.schema, depth + 1))
+            }
+            Schema::Array(items) => {
+                self.skip_blocks(depth, |scanner| scanner.skip(items, depth + 1))
+            }
+            Schema::Map(values) => self.skip_blocks(depth, |scanner| {
+                scanner.skip_bytes()?;
+                scanner.skip(values, depth + 1)
+            }),
+        }
+    }
+
+    /// Skips the blocks of an array or a map, counting the items against the limit.
+    fn skip_blocks<F>(&mut self, depth: usize, mut skip_item: F) -> std::result::Result<(), Scan>
+    where
+        F: FnMut(&mut Self) -> std::result::Result<(), Scan>,
+    {
+        exceeded("nesting depth", depth as u64 + 1, self.limits.max_depth)?;
+        let mut total: u64 = 0;
+        loop {
+            let count = self.read_long()?;
+            if count == 0 {
+                return Ok(());
+            }
+            if count < 0 {
+                // A negative count is followed by the block size in bytes.
+                self.read_long()?;
+            }
+            total = total.saturating_add(count.unsigned_abs());
+            exceeded("collection items", total, self.limits.max_collection_items)?;
+            for _ in 0..count.unsigned_abs() {
+                skip_item(self)?;
+            }
+        }
+    }
+
+    fn skip_bytes(&mut self) -> std::result::Result<(), Scan> {
+        let len = u64::try_from(self.read_long()?).map_err(|_| Scan::Malformed)?;
+        exceeded("bytes length", len, self.limits.max_bytes_length)?;
+        self.advance(usize::try_from(len).map_err(|_| Scan::Malformed)?)
+    }
+
+    fn advance(&mut self, len: usize) -> std::result::Result<(), Scan> {
+        if len > self.data.len() {
+            return Err(Scan::Malformed);
+        }
+        self.data = &self.data[len..];
+        Ok(())
+    }
+
+    /// Reads the zigzag-encoded varint.
+    fn read_long(&mut self) -> std::result::Result<i64, Scan> {
+        let mut value: u64 = 0;
+        for (i, byte) in self.data.iter().take(10).enumerate() {
+            value |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.data = &self.data[i + 1..];
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        Err(Scan::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ProtocolError;
+    use crate::limits::{DecodeLimits, Scanner};
+    use avro_rs::Schema;
+
+    #[test]
+    fn test_read_long() {
+        let limits = DecodeLimits::default();
+        let mut scanner = Scanner {
+            limits: &limits,
+            data: &[0x00, 0x01, 0x02, 0xAC, 0x02, 0xFF],
+        };
+        assert!(matches!(scanner.read_long(), Ok(0)));
+        assert!(matches!(scanner.read_long(), Ok(-1)));
+        assert!(matches!(scanner.read_long(), Ok(1)));
+        assert!(matches!(scanner.read_long(), Ok(150)));
+        assert!(scanner.read_long().is_err());
+    }
+
+    #[test]
+    fn test_exceeded() {
+        let limits = DecodeLimits::new(10, 10, 10, 10);
+        assert_eq!(limits.check_message_size(10), Ok(()));
+        assert_eq!(
+            limits.check_message_size(11),
+            Err(ProtocolError::LimitExceeded {
+                limit: String::from("message size"),
+                value: 11,
+                max: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_fixed_decimal() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Price", "fields": [
+                {"name": "amount", "type": {
+                    "type": {"type": "fixed", "name": "Amount", "size": 8},
+                    "logicalType": "decimal", "precision": 18, "scale": 2}},
+                {"name": "currency", "type": "bytes"}]}"#,
+        )
+        .unwrap();
+        let limits = DecodeLimits::new(100, 4, 10, 10);
+        let mut datum = vec![0x7E; 8];
+        datum.extend_from_slice(&[0x06, b'E', b'U', b'R']);
+        assert_eq!(limits.check_datum(&schema, &datum), Ok(()));
+
+        datum.truncate(8);
+        datum.extend_from_slice(&[0x0A, b'E', b'U', b'R', b'O', b'S']);
+        assert!(matches!(
+            limits.check_datum(&schema, &datum),
+            Err(ProtocolError::LimitExceeded { value: 5, .. })
+        ));
+    }
+}
@@ -1,13 +1,186 @@
 pub mod services;
 
-use crate::avro::{Builder, ProtocolMessage};
+use crate::avro::{
+    BuilderImpl, ProtocolMessage, KEEPALIVE_MESSAGE_SCHEMA, NOTIFY_MESSAGE_SCHEMA,
+    PING_REQUEST_RESPONSE_SCHEMA, SERVICES_FFPROBE_REQUEST_SCHEMA,
+    SERVICES_FFPROBE_RESPONSE_SCHEMA, STREAM_TRACKS_REQUEST_SCHEMA, STREAM_TRACKS_RESPONSE_SCHEMA,
+    STREAM_TRACK_UNITS_REQUEST_SCHEMA, STREAM_TRACK_UNITS_RESPONSE_SCHEMA,
+    STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA, STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA,
+    UNIT_ELEMENT_MESSAGE_SCHEMA,
+};
+use crate::error::{ProtocolError, Result};
+use crate::registry::MessageHandler;
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use services::ffprobe::{ServicesFFProbeRequest, ServicesFFProbeResponse};
+use services::keep_alive::KeepAliveMessage;
+use services::ping::PingRequestResponse;
+use services::storage::notify_message::NotifyMessage;
+use services::storage::stream_track_unit_elements::{
+    StreamTrackUnitElementsRequest, StreamTrackUnitElementsResponse,
+};
+use services::storage::stream_track_units::{StreamTrackUnitsRequest, StreamTrackUnitsResponse};
+use services::storage::stream_tracks::{StreamTracksRequest, StreamTracksResponse};
+use services::storage::unit_element_message::UnitElementMessage;
+use std::any::Any;
+use std::fmt::Debug;
+use std::sync::Arc;
 
 pub trait FromProtocolMessage {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized;
+
+    /// Decodes the JSON document of `BuilderImpl::message_to_json`.
+    fn from_json(mb: &BuilderImpl, json: &str) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::load(&mb.message_from_json(json)?)
+    }
 }
 
 pub trait ToProtocolMessage {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage>;
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage>;
+
+    /// Renders the message as the JSON document of `BuilderImpl::message_to_json`.
+    fn to_json(&self, mb: &BuilderImpl) -> Result<String> {
+        mb.message_to_json(&self.save(mb)?)
+    }
+}
+
+/// A message of a schema registered at runtime.
+pub trait CustomMessage: ToProtocolMessage + Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn eq_custom(&self, other: &dyn CustomMessage) -> bool;
+}
+
+impl<T> CustomMessage for T
+where
+    T: ToProtocolMessage + PartialEq + Debug + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_custom(&self, other: &dyn CustomMessage) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+impl PartialEq for dyn CustomMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.eq_custom(other)
+    }
+}
+
+/// The undecoded Avro value serves as the message of a schema without a dedicated type.
+impl FromProtocolMessage for ProtocolMessage {
+    fn load(message: &ProtocolMessage) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(message.clone())
+    }
+}
+
+impl ToProtocolMessage for ProtocolMessage {
+    fn save(&self, _mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        Ok(self.clone())
+    }
+}
+
+pub(crate) fn check_schema(message: &ProtocolMessage, expected: &str) -> Result<()> {
+    if message.schema != expected {
+        Err(ProtocolError::SchemaMismatch {
+            expected: String::from(expected),
+            found: message.schema.clone(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! any_message {
+    ($($variant:ident => $schema:ident),* $(,)?) => {
+        /// Any message of the protocol, dispatched by the schema carried in the envelope.
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum AnyMessage {
+            $($variant($variant),)*
+            Custom {
+                schema: String,
+                message: Arc<dyn CustomMessage>,
+            },
+        }
+
+        impl AnyMessage {
+            pub fn schema(&self) -> &str {
+                match self {
+                    $(AnyMessage::$variant(_) => $schema,)*
+                    AnyMessage::Custom { schema, .. } => schema,
+                }
+            }
+        }
+
+        pub(crate) fn builtin_handlers() -> Vec<(&'static str, MessageHandler)> {
+            vec![
+                $(
+                    (
+                        $schema,
+                        MessageHandler {
+                            decode: |m| $variant::load(m).map(AnyMessage::$variant),
+                            #[cfg(feature = "python")]
+                            extract: Some(|ob, _| Ok(AnyMessage::$variant(ob.extract()?))),
+                        },
+                    ),
+                )*
+            ]
+        }
+
+        impl ToProtocolMessage for AnyMessage {
+            fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+                match self {
+                    $(AnyMessage::$variant(m) => m.save(mb),)*
+                    AnyMessage::Custom { message, .. } => message.save(mb),
+                }
+            }
+        }
+
+        #[cfg(feature = "python")]
+        impl AnyMessage {
+            /// Converts a built-in message into its Python class, handing a custom one back.
+            pub(crate) fn into_builtin_py(
+                self,
+                py: Python,
+            ) -> std::result::Result<PyObject, Arc<dyn CustomMessage>> {
+                match self {
+                    $(AnyMessage::$variant(m) => Ok(m.into_py(py)),)*
+                    AnyMessage::Custom { message, .. } => Err(message),
+                }
+            }
+        }
+
+        $(
+            impl From<$variant> for AnyMessage {
+                fn from(m: $variant) -> Self {
+                    AnyMessage::$variant(m)
+                }
+            }
+        )*
+    };
+}
+
+any_message! {
+    UnitElementMessage => UNIT_ELEMENT_MESSAGE_SCHEMA,
+    NotifyMessage => NOTIFY_MESSAGE_SCHEMA,
+    PingRequestResponse => PING_REQUEST_RESPONSE_SCHEMA,
+    ServicesFFProbeRequest => SERVICES_FFPROBE_REQUEST_SCHEMA,
+    ServicesFFProbeResponse => SERVICES_FFPROBE_RESPONSE_SCHEMA,
+    StreamTrackUnitElementsRequest => STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA,
+    StreamTrackUnitElementsResponse => STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA,
+    StreamTracksRequest => STREAM_TRACKS_REQUEST_SCHEMA,
+    StreamTracksResponse => STREAM_TRACKS_RESPONSE_SCHEMA,
+    StreamTrackUnitsRequest => STREAM_TRACK_UNITS_REQUEST_SCHEMA,
+    StreamTrackUnitsResponse => STREAM_TRACK_UNITS_RESPONSE_SCHEMA,
+    KeepAliveMessage => KEEPALIVE_MESSAGE_SCHEMA,
 }
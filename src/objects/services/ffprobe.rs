@@ -1,15 +1,17 @@
 use crate::avro::{
-    Builder, ProtocolMessage, SERVICES_FFPROBE_REQUEST_SCHEMA, SERVICES_FFPROBE_RESPONSE_SCHEMA,
+    BuilderImpl, ProtocolMessage, SERVICES_FFPROBE_REQUEST_SCHEMA, SERVICES_FFPROBE_RESPONSE_SCHEMA,
 };
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
-use crate::utils::{gen_hash_map, value_to_string};
+use crate::error::{ProtocolError, Result};
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
+use crate::record::RecordFields;
+use crate::utils::gen_hash_map;
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum ServicesFFProbeResponseType {
     Accepted,
     Complete,
@@ -19,42 +21,42 @@ pub enum ServicesFFProbeResponseType {
 
 pub fn get_services_ffprobe_response_type_avro(
     response_type: &ServicesFFProbeResponseType,
-) -> Value {
+) -> Result<Value> {
     match response_type {
-        ServicesFFProbeResponseType::Accepted => Value::Enum(0, "ACCEPTED".into()),
-        ServicesFFProbeResponseType::Complete => Value::Enum(1, "COMPLETE".into()),
-        ServicesFFProbeResponseType::Error => Value::Enum(2, "ERROR".into()),
-        ServicesFFProbeResponseType::NotImplemented => {
-            panic!("Not supported ffprobe response type")
-        }
+        ServicesFFProbeResponseType::Accepted => Ok(Value::Enum(0, "ACCEPTED".into())),
+        ServicesFFProbeResponseType::Complete => Ok(Value::Enum(1, "COMPLETE".into())),
+        ServicesFFProbeResponseType::Error => Ok(Value::Enum(2, "ERROR".into())),
+        ServicesFFProbeResponseType::NotImplemented => Err(ProtocolError::UnknownEnumSymbol {
+            enum_name: String::from("ServicesFFProbeResponseType"),
+            symbol: String::from("NotImplemented"),
+        }),
     }
 }
 
-fn get_services_ffprobe_response_type_enum(response_type: &str) -> ServicesFFProbeResponseType {
+fn get_services_ffprobe_response_type_enum(
+    response_type: &str,
+) -> Result<ServicesFFProbeResponseType> {
     match response_type {
-        "ACCEPTED" => ServicesFFProbeResponseType::Accepted,
-        "COMPLETE" => ServicesFFProbeResponseType::Complete,
-        "ERROR" => ServicesFFProbeResponseType::Error,
-        _ => ServicesFFProbeResponseType::NotImplemented,
+        "ACCEPTED" => Ok(ServicesFFProbeResponseType::Accepted),
+        "COMPLETE" => Ok(ServicesFFProbeResponseType::Complete),
+        "ERROR" => Ok(ServicesFFProbeResponseType::Error),
+        _ => Err(ProtocolError::UnknownEnumSymbol {
+            enum_name: String::from("ServicesFFProbeResponseType"),
+            symbol: String::from(response_type),
+        }),
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct ServicesFFProbeRequest {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub topic: String,
-    #[pyo3(get, set)]
     pub url: String,
-    #[pyo3(get, set)]
     pub attributes: HashMap<String, String>,
 }
 
-#[pymethods]
 impl ServicesFFProbeRequest {
-    #[new]
     pub fn new(
         request_id: i64,
         topic: String,
@@ -68,6 +70,20 @@ impl ServicesFFProbeRequest {
             attributes,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ServicesFFProbeRequest {
+    #[new]
+    fn py_new(
+        request_id: i64,
+        topic: String,
+        url: String,
+        attributes: HashMap<String, String>,
+    ) -> Self {
+        Self::new(request_id, topic, url, attributes)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -77,26 +93,23 @@ impl ServicesFFProbeRequest {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = SERVICES_FFPROBE_REQUEST_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct ServicesFFProbeResponse {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub response_type: ServicesFFProbeResponseType,
-    #[pyo3(get, set)]
     pub time_spent: i64,
-    #[pyo3(get, set)]
     pub streams: Vec<HashMap<String, String>>,
 }
 
-#[pymethods]
 impl ServicesFFProbeResponse {
-    #[new]
     pub fn new(
         request_id: i64,
         response_type: ServicesFFProbeResponseType,
@@ -110,6 +123,20 @@ impl ServicesFFProbeResponse {
             streams,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl ServicesFFProbeResponse {
+    #[new]
+    fn py_new(
+        request_id: i64,
+        response_type: ServicesFFProbeResponseType,
+        time_spent: i64,
+        streams: Vec<HashMap<String, String>>,
+    ) -> Self {
+        Self::new(request_id, response_type, time_spent, streams)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -119,177 +146,118 @@ impl ServicesFFProbeResponse {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = SERVICES_FFPROBE_RESPONSE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for ServicesFFProbeRequest {
-    fn load(message: &ProtocolMessage) -> Option<ServicesFFProbeRequest> {
-        if message.schema != SERVICES_FFPROBE_REQUEST_SCHEMA {
-            return None;
-        }
-
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::String(topic)), (_, Value::String(url)), (_, Value::Map(attributes))] => {
-                    Some(ServicesFFProbeRequest {
-                        request_id: *request_id,
-                        topic: topic.clone(),
-                        url: url.clone(),
-                        attributes: attributes
-                            .iter()
-                            .map(|x| {
-                                (
-                                    x.0.clone(),
-                                    value_to_string(x.1)
-                                        .or_else(|| Some(String::from("")))
-                                        .unwrap(),
-                                )
-                            })
-                            .collect(),
-                    })
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to FFprobe Request");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+    fn load(message: &ProtocolMessage) -> Result<ServicesFFProbeRequest> {
+        check_schema(message, SERVICES_FFPROBE_REQUEST_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(ServicesFFProbeRequest {
+            request_id: fields.get("request_id")?,
+            topic: fields.get("topic")?,
+            url: fields.get("url")?,
+            attributes: fields.get_or_default("attributes")?,
+        })
     }
 }
 
 impl FromProtocolMessage for ServicesFFProbeResponse {
-    fn load(message: &ProtocolMessage) -> Option<ServicesFFProbeResponse> {
-        if message.schema != SERVICES_FFPROBE_RESPONSE_SCHEMA {
-            return None;
-        }
-
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::Enum(_, response_type)), (_, Value::Long(time_spent)), (_, Value::Array(streams))] =>
-                {
-                    let mut response_streams: Vec<HashMap<String, String>> = Default::default();
-                    for s in streams {
-                        match s {
-                            Value::Map(attributes) => {
-                                let attributes: HashMap<String, String> = attributes
-                                    .iter()
-                                    .map(|kv| {
-                                        (
-                                            kv.0.clone(),
-                                            value_to_string(kv.1)
-                                                .unwrap_or_else(|| String::from("")),
-                                        )
-                                    })
-                                    .collect();
-                                response_streams.push(attributes);
-                            }
-                            _ => panic!(
-                                "Unexpected structure found, stream attributes must be a `map`"
-                            ),
-                        }
-                    }
-
-                    Some(ServicesFFProbeResponse {
-                        request_id: *request_id,
-                        response_type: get_services_ffprobe_response_type_enum(
-                            response_type.as_str(),
-                        ),
-                        time_spent: *time_spent,
-                        streams: response_streams,
-                    })
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to FFprobe Response");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+    fn load(message: &ProtocolMessage) -> Result<ServicesFFProbeResponse> {
+        check_schema(message, SERVICES_FFPROBE_RESPONSE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(ServicesFFProbeResponse {
+            request_id: fields.get("request_id")?,
+            response_type: get_services_ffprobe_response_type_enum(
+                fields.enum_symbol("response_type")?,
+            )?,
+            time_spent: fields.get("time_spent")?,
+            streams: fields.array("streams")?,
+        })
     }
 }
 
 impl ToProtocolMessage for ServicesFFProbeRequest {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut object = mb.get_record(SERVICES_FFPROBE_REQUEST_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut object = mb.get_record(SERVICES_FFPROBE_REQUEST_SCHEMA)?;
         object.put("request_id", Value::Long(self.request_id));
         object.put("topic", Value::String(self.topic.clone()));
         object.put("url", Value::String(self.url.clone()));
         object.put("attributes", gen_hash_map(&self.attributes));
-        Some(ProtocolMessage {
-            schema: String::from(SERVICES_FFPROBE_REQUEST_SCHEMA),
-            object: Value::from(object),
-        })
+        Ok(ProtocolMessage::new(
+            SERVICES_FFPROBE_REQUEST_SCHEMA,
+            Value::from(object),
+        ))
     }
 }
 
 impl ToProtocolMessage for ServicesFFProbeResponse {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut object = mb.get_record(SERVICES_FFPROBE_RESPONSE_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut object = mb.get_record(SERVICES_FFPROBE_RESPONSE_SCHEMA)?;
         object.put("request_id", Value::Long(self.request_id));
         object.put(
             "response_type",
-            get_services_ffprobe_response_type_avro(&self.response_type),
+            get_services_ffprobe_response_type_avro(&self.response_type)?,
         );
         object.put("time_spent", Value::Long(self.time_spent));
         let streams_array: Vec<Value> = self.streams.iter().map(gen_hash_map).collect();
         object.put("streams", Value::Array(streams_array));
-        Some(ProtocolMessage {
-            schema: String::from(SERVICES_FFPROBE_RESPONSE_SCHEMA),
-            object: Value::from(object),
-        })
+        Ok(ProtocolMessage::new(
+            SERVICES_FFPROBE_RESPONSE_SCHEMA,
+            Value::from(object),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::ffprobe::{
         ServicesFFProbeRequest, ServicesFFProbeResponse, ServicesFFProbeResponseType,
     };
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
-    use crate::utils::get_avro_path;
+    use crate::objects::{AnyMessage, FromProtocolMessage, ToProtocolMessage};
+    use avro_rs::types::Value;
     use std::collections::HashMap;
 
     #[test]
     fn test_load_save_req() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
         let req = ServicesFFProbeRequest::new(
             0,
             String::from("test"),
             String::from("/dev/video0"),
             HashMap::from([("attribute".into(), "value".into())]),
         );
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let new_req_opt = ServicesFFProbeRequest::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
+        assert_eq!(AnyMessage::ServicesFFProbeRequest(req), new_req);
+    }
 
-        let new_req = new_req_opt.unwrap();
+    #[test]
+    fn test_load_req_without_attributes() {
+        let codec = Codec::default();
+        let req = ServicesFFProbeRequest::new(
+            0,
+            String::from("test"),
+            String::from("/dev/video0"),
+            HashMap::default(),
+        );
 
-        assert_eq!(req, new_req);
+        let mut message = req.save(codec.builder()).unwrap();
+        if let Value::Record(fields) = &mut message.object {
+            fields.retain(|(name, _)| name != "attributes");
+        }
+        assert_eq!(ServicesFFProbeRequest::load(&message), Ok(req));
     }
 
     #[test]
     fn test_load_save_resp() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
         let res = ServicesFFProbeResponse::new(
             1,
             ServicesFFProbeResponseType::Accepted,
@@ -299,23 +267,9 @@ mod tests {
                 HashMap::from([("x".to_string(), "y".to_string())]),
             ],
         );
-        let res_envelope_opt = res.save(&mb);
-        assert!(res_envelope_opt.is_some());
-
-        let res_envelope = res_envelope_opt.unwrap();
-        let res_serialized = mb.save_from_avro(res_envelope);
-
-        let res_envelope_opt = mb.load_to_avro(res_serialized);
-        assert!(res_envelope_opt.is_some());
-
-        let res_envelope = res_envelope_opt.unwrap();
-
-        let new_res_opt = ServicesFFProbeResponse::load(&res_envelope);
-
-        assert!(new_res_opt.is_some());
-
-        let new_res = new_res_opt.unwrap();
+        let res_serialized = codec.encode(&res).unwrap();
+        let new_res = codec.decode(&res_serialized).unwrap();
 
-        assert_eq!(res, new_res);
+        assert_eq!(AnyMessage::ServicesFFProbeResponse(res), new_res);
     }
 }
@@ -1,30 +1,27 @@
-use crate::avro::{Builder, ProtocolMessage, PING_REQUEST_RESPONSE_SCHEMA};
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+use crate::avro::{BuilderImpl, ProtocolMessage, PING_REQUEST_RESPONSE_SCHEMA};
+use crate::error::{ProtocolError, Result};
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
+use crate::record::RecordFields;
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum PingRequestResponseType {
     Request,
     Response,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct PingRequestResponse {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub topic: String,
-    #[pyo3(get, set)]
     pub mtype: PingRequestResponseType,
 }
 
-#[pymethods]
 impl PingRequestResponse {
-    #[new]
     pub fn new(
         request_id: i64,
         topic: String,
@@ -36,6 +33,15 @@ impl PingRequestResponse {
             mtype,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl PingRequestResponse {
+    #[new]
+    fn py_new(request_id: i64, topic: String, mtype: PingRequestResponseType) -> Self {
+        Self::new(request_id, topic, mtype)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -45,47 +51,40 @@ impl PingRequestResponse {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = PING_REQUEST_RESPONSE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for PingRequestResponse {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != PING_REQUEST_RESPONSE_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::String(topic)), (_, Value::Enum(_index, ping_m_type))] => {
-                    Some(PingRequestResponse {
-                        request_id: *request_id,
-                        topic: topic.clone(),
-                        mtype: if ping_m_type.as_str() == "REQUEST" {
-                            PingRequestResponseType::Request
-                        } else {
-                            PingRequestResponseType::Response
-                        },
+        check_schema(message, PING_REQUEST_RESPONSE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(PingRequestResponse {
+            request_id: fields.get("request_id")?,
+            topic: fields.get("topic")?,
+            mtype: match fields.enum_symbol("type")? {
+                "REQUEST" => PingRequestResponseType::Request,
+                "RESPONSE" => PingRequestResponseType::Response,
+                symbol => {
+                    return Err(ProtocolError::UnknownEnumSymbol {
+                        enum_name: String::from("PingRequestResponseType"),
+                        symbol: String::from(symbol),
                     })
                 }
-                _ => {
-                    warn!("Unable to match AVRO Record to to PingRequestResponse");
-                    None
-                }
             },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        })
     }
 }
 
 impl ToProtocolMessage for PingRequestResponse {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut object = mb.get_record(PING_REQUEST_RESPONSE_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut object = mb.get_record(PING_REQUEST_RESPONSE_SCHEMA)?;
         object.put("request_id", Value::Long(self.request_id));
         object.put("topic", Value::String(self.topic.clone()));
         match self.mtype {
@@ -97,42 +96,30 @@ impl ToProtocolMessage for PingRequestResponse {
             }
         }
 
-        Some(ProtocolMessage {
-            schema: String::from(PING_REQUEST_RESPONSE_SCHEMA),
-            object: Value::from(object),
-        })
+        Ok(ProtocolMessage::new(
+            PING_REQUEST_RESPONSE_SCHEMA,
+            Value::from(object),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::avro::{ProtocolMessage, PING_REQUEST_RESPONSE_SCHEMA};
+    use crate::codec::Codec;
+    use crate::error::ProtocolError;
     use crate::objects::services::ping::{PingRequestResponse, PingRequestResponseType};
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
-    use crate::utils::get_avro_path;
+    use crate::objects::{AnyMessage, FromProtocolMessage};
+    use avro_rs::types::Value;
 
     fn test_load_save_req_rep(mt: PingRequestResponseType) {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
         let req = PingRequestResponse::new(0, String::from("test"), mt);
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let new_req_opt = PingRequestResponse::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
-
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::PingRequestResponse(req), new_req);
     }
 
     #[test]
@@ -140,4 +127,34 @@ mod tests {
         test_load_save_req_rep(PingRequestResponseType::Request);
         test_load_save_req_rep(PingRequestResponseType::Response);
     }
+
+    #[test]
+    fn test_load_evolved_record() {
+        let mut message = ProtocolMessage::new(
+            PING_REQUEST_RESPONSE_SCHEMA,
+            Value::Record(vec![
+                ("type".into(), Value::Enum(1, "RESPONSE".into())),
+                ("added".into(), Value::Boolean(true)),
+                ("topic".into(), Value::String("test".into())),
+                ("request_id".into(), Value::Long(3)),
+            ]),
+        );
+        assert_eq!(
+            PingRequestResponse::load(&message),
+            Ok(PingRequestResponse::new(
+                3,
+                String::from("test"),
+                PingRequestResponseType::Response
+            ))
+        );
+
+        message.object = Value::Record(vec![("request_id".into(), Value::Long(3))]);
+        assert_eq!(
+            PingRequestResponse::load(&message),
+            Err(ProtocolError::MissingField {
+                schema: String::from(PING_REQUEST_RESPONSE_SCHEMA),
+                field: String::from("topic"),
+            })
+        );
+    }
 }
@@ -1,24 +1,22 @@
-use crate::avro::{Builder, ProtocolMessage, NOTIFY_MESSAGE_SCHEMA};
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+use crate::avro::{BuilderImpl, ProtocolMessage, NOTIFY_MESSAGE_SCHEMA};
+use crate::error::{ProtocolError, Result};
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
 use crate::primitives::{NotifyType, NotifyTypeImpl, Unit};
+use crate::record::RecordFields;
+use crate::utils::checked_cast;
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct NotifyMessage {
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub saved_ms: u64,
-    #[pyo3(get, set)]
     pub notify_type: NotifyType,
 }
 
-#[pymethods]
 impl NotifyMessage {
-    #[new]
     pub fn new(stream_unit: Unit, saved_ms: u64, notify_type: NotifyType) -> Self {
         NotifyMessage {
             stream_unit,
@@ -26,6 +24,15 @@ impl NotifyMessage {
             notify_type,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl NotifyMessage {
+    #[new]
+    fn py_new(stream_unit: Unit, saved_ms: u64, notify_type: NotifyType) -> Self {
+        Self::new(stream_unit, saved_ms, notify_type)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -35,64 +42,48 @@ impl NotifyMessage {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = NOTIFY_MESSAGE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for NotifyMessage {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != NOTIFY_MESSAGE_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Record(stream_unit_fields)), (_, Value::Int(last_element)), (_, Value::Long(saved_ms)), (_, Value::Enum(_index, notify_type))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] => {
-                            Some(NotifyMessage {
-                                stream_unit: Unit::new(
-                                    stream_name.clone(),
-                                    track_name.clone(),
-                                    track_type.clone(),
-                                    *unit,
-                                ),
-                                saved_ms: *saved_ms as u64,
-                                notify_type: match notify_type.as_str() {
-                                    "READY" => NotifyType::ready(*last_element as i16),
-                                    "NEW" => NotifyType::new(),
-                                    _ => NotifyType {
-                                        obj: NotifyTypeImpl::NotImplemented,
-                                    },
-                                },
-                            })
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to NotifyMessage");
-                    None
+        check_schema(message, NOTIFY_MESSAGE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(NotifyMessage {
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            saved_ms: checked_cast("saved_ms", fields.get::<i64>("saved_ms")?)?,
+            notify_type: match fields.enum_symbol("notify_type")? {
+                "READY" => NotifyType::ready(checked_cast(
+                    "last_element",
+                    fields.get::<i32>("last_element")?,
+                )?),
+                "NEW" => NotifyType::new(),
+                symbol => {
+                    return Err(ProtocolError::UnknownEnumSymbol {
+                        enum_name: String::from("NotifyType"),
+                        symbol: String::from(symbol),
+                    })
                 }
             },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        })
     }
 }
 
 impl ToProtocolMessage for NotifyMessage {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(NOTIFY_MESSAGE_SCHEMA);
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
-        obj.put("saved_ms", Value::Long(self.saved_ms as i64));
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(NOTIFY_MESSAGE_SCHEMA)?;
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
+        obj.put(
+            "saved_ms",
+            Value::Long(checked_cast("saved_ms", self.saved_ms)?),
+        );
         match &self.notify_type.obj {
             NotifyTypeImpl::Ready(elt) => {
                 obj.put("notify_type", Value::Enum(0, "READY".into()));
@@ -103,28 +94,30 @@ impl ToProtocolMessage for NotifyMessage {
                 obj.put("last_element", Value::Int(-1));
             }
             NotifyTypeImpl::NotImplemented => {
-                panic!("Unable to handle unsupported NotifyType");
+                return Err(ProtocolError::UnknownEnumSymbol {
+                    enum_name: String::from("NotifyType"),
+                    symbol: String::from("NotImplemented"),
+                });
             }
         }
 
-        Some(ProtocolMessage {
-            schema: String::from(NOTIFY_MESSAGE_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            NOTIFY_MESSAGE_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::storage::notify_message::NotifyMessage;
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+    use crate::objects::AnyMessage;
     use crate::primitives::{pack_stream_name, pack_track_name, NotifyType, Unit};
-    use crate::utils::get_avro_path;
     use uuid::Uuid;
 
     fn test_load_save_req_int(notify_type: NotifyType) {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -141,24 +134,10 @@ mod tests {
             notify_type,
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = NotifyMessage::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::NotifyMessage(req), new_req);
     }
 
     #[test]
@@ -1,31 +1,27 @@
 use crate::avro::{
-    Builder, ProtocolMessage, STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA,
+    BuilderImpl, ProtocolMessage, STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA,
     STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA,
 };
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+use crate::error::Result;
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
 use crate::primitives::{ElementType, Payload, Unit};
-use crate::utils::value_to_string;
+use crate::record::RecordFields;
+use crate::utils::{checked_cast, gen_hash_map};
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTrackUnitElementsRequest {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub topic: String,
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub max_element: ElementType,
 }
 
-#[pymethods]
 impl StreamTrackUnitElementsRequest {
-    #[new]
     pub fn new(
         request_id: i64,
         topic: String,
@@ -39,6 +35,15 @@ impl StreamTrackUnitElementsRequest {
             max_element,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTrackUnitElementsRequest {
+    #[new]
+    fn py_new(request_id: i64, topic: String, stream_unit: Unit, max_element: ElementType) -> Self {
+        Self::new(request_id, topic, stream_unit, max_element)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -48,83 +53,53 @@ impl StreamTrackUnitElementsRequest {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for StreamTrackUnitElementsRequest {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::String(topic)), (_, Value::Record(stream_unit_fields)), (_, Value::Long(max_element))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] => {
-                            Some(StreamTrackUnitElementsRequest {
-                                request_id: *request_id,
-                                topic: topic.clone(),
-                                stream_unit: Unit::new(
-                                    stream_name.clone(),
-                                    track_name.clone(),
-                                    track_type.clone(),
-                                    *unit,
-                                ),
-                                max_element: *max_element as i16,
-                            })
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTrackUnitElementsRequest");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(StreamTrackUnitElementsRequest {
+            request_id: fields.get("request_id")?,
+            topic: fields.get("topic")?,
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            max_element: checked_cast("max_element", fields.get::<i64>("max_element")?)?,
+        })
     }
 }
 
 impl ToProtocolMessage for StreamTrackUnitElementsRequest {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
         obj.put("topic", Value::String(self.topic.clone()));
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
         obj.put("max_element", Value::Long(self.max_element.into()));
 
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            STREAM_TRACK_UNIT_ELEMENTS_REQUEST_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTrackUnitElementsResponse {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub values: Vec<Payload>,
 }
 
-#[pymethods]
 impl StreamTrackUnitElementsResponse {
-    #[new]
     pub fn new(request_id: i64, stream_unit: Unit, values: Vec<Payload>) -> Self {
         StreamTrackUnitElementsResponse {
             request_id,
@@ -132,6 +107,15 @@ impl StreamTrackUnitElementsResponse {
             values,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTrackUnitElementsResponse {
+    #[new]
+    fn py_new(request_id: i64, stream_unit: Unit, values: Vec<Payload>) -> Self {
+        Self::new(request_id, stream_unit, values)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -141,132 +125,78 @@ impl StreamTrackUnitElementsResponse {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
+fn payload_from_avro(fields: &RecordFields) -> Result<Payload> {
+    Ok(Payload {
+        data: fields.get("data")?,
+        attributes: fields.get_or_default("attributes")?,
+    })
+}
+
 impl FromProtocolMessage for StreamTrackUnitElementsResponse {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::Record(stream_unit_fields)), (_, Value::Array(values))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] =>
-                        {
-                            fn to_payload(v: &Value) -> Option<Payload> {
-                                match v {
-                                    Value::Record(fields) => match fields.as_slice() {
-                                        [(_, Value::Bytes(data)), (_, Value::Map(attributes))] => {
-                                            Some(Payload {
-                                                data: data.clone(),
-                                                attributes: attributes
-                                                    .iter()
-                                                    .map(|x| {
-                                                        (
-                                                            x.0.clone(),
-                                                            value_to_string(x.1)
-                                                                .or_else(|| Some(String::from("")))
-                                                                .unwrap(),
-                                                        )
-                                                    })
-                                                    .collect(),
-                                            })
-                                        }
-                                        _ => None,
-                                    },
-                                    _ => None,
-                                }
-                            }
-
-                            let values_parsed: Vec<_> =
-                                values.iter().filter_map(to_payload).collect();
-
-                            if values_parsed.len() < values.len() {
-                                warn!("Not all payload values were parsed correctly");
-                                None
-                            } else {
-                                Some(StreamTrackUnitElementsResponse {
-                                    request_id: *request_id,
-                                    stream_unit: Unit::new(
-                                        stream_name.clone(),
-                                        track_name.clone(),
-                                        track_type.clone(),
-                                        *unit,
-                                    ),
-                                    values: values_parsed,
-                                })
-                            }
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTrackUnitElementsRequest");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        let values = fields
+            .records("values")?
+            .iter()
+            .map(payload_from_avro)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(StreamTrackUnitElementsResponse {
+            request_id: fields.get("request_id")?,
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            values,
+        })
     }
 }
 
 fn payload_to_avro(p: &Payload) -> Value {
     Value::Record(vec![
         ("data".into(), Value::Bytes(p.data.clone())),
-        (
-            "attributes".into(),
-            Value::Map(
-                p.attributes
-                    .iter()
-                    .map(|x| (x.0.clone(), Value::String(x.1.clone())))
-                    .collect(),
-            ),
-        ),
+        ("attributes".into(), gen_hash_map(&p.attributes)),
     ])
 }
 
 impl ToProtocolMessage for StreamTrackUnitElementsResponse {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
 
         let values: Vec<Value> = self.values.iter().map(payload_to_avro).collect();
         obj.put("values", Value::Array(values));
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            STREAM_TRACK_UNIT_ELEMENTS_RESPONSE_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::storage::stream_track_unit_elements::{
         StreamTrackUnitElementsRequest, StreamTrackUnitElementsResponse,
     };
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+    use crate::objects::{AnyMessage, FromProtocolMessage, ToProtocolMessage};
     use crate::primitives::{pack_stream_name, pack_track_name, Payload, Unit};
-    use crate::utils::get_avro_path;
+    use avro_rs::types::Value;
     use std::collections::HashMap;
     use uuid::Uuid;
 
     #[test]
     fn test_load_save_req() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -284,29 +214,15 @@ mod tests {
             100,
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let new_req_opt = StreamTrackUnitElementsRequest::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
-
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::StreamTrackUnitElementsRequest(req), new_req);
     }
 
     #[test]
     fn test_load_save_rep() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -332,23 +248,42 @@ mod tests {
             ],
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = StreamTrackUnitElementsResponse::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
+        assert_eq!(AnyMessage::StreamTrackUnitElementsResponse(req), new_req);
+    }
 
-        let new_req = new_req_opt.unwrap();
+    #[test]
+    fn test_load_rep_without_attributes() {
+        let codec = Codec::default();
+        let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
+        let rep = StreamTrackUnitElementsResponse::new(
+            1,
+            Unit::new(
+                pack_stream_name(&stream_uuid).to_vec(),
+                pack_track_name(&String::from("test")).unwrap().to_vec(),
+                String::from("VIDEO"),
+                3,
+            ),
+            vec![Payload {
+                data: vec![0, 1, 2],
+                attributes: HashMap::default(),
+            }],
+        );
 
-        assert_eq!(req, new_req);
+        let mut message = rep.save(codec.builder()).unwrap();
+        if let Value::Record(fields) = &mut message.object {
+            for (_, values) in fields.iter_mut().filter(|(name, _)| name == "values") {
+                if let Value::Array(payloads) = values {
+                    for payload in payloads {
+                        if let Value::Record(payload) = payload {
+                            payload.retain(|(name, _)| name != "attributes");
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(StreamTrackUnitElementsResponse::load(&message), Ok(rep));
     }
 }
@@ -1,30 +1,27 @@
 use crate::avro::{
-    Builder, ProtocolMessage, STREAM_TRACK_UNITS_REQUEST_SCHEMA, STREAM_TRACK_UNITS_RESPONSE_SCHEMA,
+    BuilderImpl, ProtocolMessage, STREAM_TRACK_UNITS_REQUEST_SCHEMA,
+    STREAM_TRACK_UNITS_RESPONSE_SCHEMA,
 };
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+use crate::error::Result;
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
 use crate::primitives::Unit;
+use crate::record::RecordFields;
+use crate::utils::checked_cast;
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTrackUnitsRequest {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub topic: String,
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub from_ms: u128,
-    #[pyo3(get, set)]
     pub to_ms: u128,
 }
 
-#[pymethods]
 impl StreamTrackUnitsRequest {
-    #[new]
     pub fn new(
         request_id: i64,
         topic: String,
@@ -40,6 +37,21 @@ impl StreamTrackUnitsRequest {
             to_ms,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTrackUnitsRequest {
+    #[new]
+    fn py_new(
+        request_id: i64,
+        topic: String,
+        stream_unit: Unit,
+        from_ms: u128,
+        to_ms: u128,
+    ) -> Self {
+        Self::new(request_id, topic, stream_unit, from_ms, to_ms)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -49,85 +61,59 @@ impl StreamTrackUnitsRequest {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACK_UNITS_REQUEST_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for StreamTrackUnitsRequest {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::String(topic)), (_, Value::Record(stream_unit_fields)), (_, Value::Long(from_ms)), (_, Value::Long(to_ms))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] => {
-                            Some(StreamTrackUnitsRequest {
-                                request_id: *request_id,
-                                topic: topic.clone(),
-                                stream_unit: Unit::new(
-                                    stream_name.clone(),
-                                    track_name.clone(),
-                                    track_type.clone(),
-                                    *unit,
-                                ),
-                                from_ms: *from_ms as u128,
-                                to_ms: *to_ms as u128,
-                            })
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTrackUnitsRequest");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACK_UNITS_REQUEST_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(StreamTrackUnitsRequest {
+            request_id: fields.get("request_id")?,
+            topic: fields.get("topic")?,
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            from_ms: checked_cast("from_ms", fields.get::<i64>("from_ms")?)?,
+            to_ms: checked_cast("to_ms", fields.get::<i64>("to_ms")?)?,
+        })
     }
 }
 
 impl ToProtocolMessage for StreamTrackUnitsRequest {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACK_UNITS_REQUEST_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACK_UNITS_REQUEST_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
         obj.put("topic", Value::String(self.topic.clone()));
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
-        obj.put("from_ms", Value::Long(i64::try_from(self.from_ms).unwrap()));
-        obj.put("to_ms", Value::Long(i64::try_from(self.to_ms).unwrap()));
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACK_UNITS_REQUEST_SCHEMA),
-            object: Value::from(obj),
-        })
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
+        obj.put(
+            "from_ms",
+            Value::Long(checked_cast("from_ms", self.from_ms)?),
+        );
+        obj.put("to_ms", Value::Long(checked_cast("to_ms", self.to_ms)?));
+        Ok(ProtocolMessage::new(
+            STREAM_TRACK_UNITS_REQUEST_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTrackUnitsResponse {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub from_ms: u128,
-    #[pyo3(get, set)]
     pub to_ms: u128,
-    #[pyo3(get, set)]
     pub units: Vec<i64>,
 }
 
-#[pymethods]
 impl StreamTrackUnitsResponse {
-    #[new]
     pub fn new(
         request_id: i64,
         stream_unit: Unit,
@@ -143,6 +129,21 @@ impl StreamTrackUnitsResponse {
             units,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTrackUnitsResponse {
+    #[new]
+    fn py_new(
+        request_id: i64,
+        stream_unit: Unit,
+        from_ms: u128,
+        to_ms: u128,
+        units: Vec<i64>,
+    ) -> Self {
+        Self::new(request_id, stream_unit, from_ms, to_ms, units)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -152,96 +153,62 @@ impl StreamTrackUnitsResponse {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACK_UNITS_RESPONSE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for StreamTrackUnitsResponse {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::Record(stream_unit_fields)), (_, Value::Long(from_ms)), (_, Value::Long(to_ms)), (_, Value::Array(units))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] =>
-                        {
-                            let units_parsed: Vec<_> = units
-                                .iter()
-                                .filter_map(|x| match x {
-                                    Value::Long(value) => Some(*value),
-                                    _ => None,
-                                })
-                                .collect();
-
-                            if units_parsed.len() < units.len() {
-                                warn!("Not all payload units were parsed correctly");
-                                None
-                            } else {
-                                Some(StreamTrackUnitsResponse {
-                                    request_id: *request_id,
-                                    stream_unit: Unit::new(
-                                        stream_name.clone(),
-                                        track_name.clone(),
-                                        track_type.clone(),
-                                        *unit,
-                                    ),
-                                    from_ms: *from_ms as u128,
-                                    to_ms: *to_ms as u128,
-                                    units: units_parsed,
-                                })
-                            }
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTrackUnitsResponse");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACK_UNITS_RESPONSE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(StreamTrackUnitsResponse {
+            request_id: fields.get("request_id")?,
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            from_ms: checked_cast("from_ms", fields.get::<i64>("from_ms")?)?,
+            to_ms: checked_cast("to_ms", fields.get::<i64>("to_ms")?)?,
+            units: fields.array("units")?,
+        })
     }
 }
 
 impl ToProtocolMessage for StreamTrackUnitsResponse {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACK_UNITS_RESPONSE_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACK_UNITS_RESPONSE_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
-        obj.put("from_ms", Value::Long(i64::try_from(self.from_ms).unwrap()));
-        obj.put("to_ms", Value::Long(i64::try_from(self.to_ms).unwrap()));
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
+        obj.put(
+            "from_ms",
+            Value::Long(checked_cast("from_ms", self.from_ms)?),
+        );
+        obj.put("to_ms", Value::Long(checked_cast("to_ms", self.to_ms)?));
         let values: Vec<Value> = self.units.iter().map(|x| Value::Long(*x)).collect();
         obj.put("units", Value::Array(values));
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACK_UNITS_RESPONSE_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            STREAM_TRACK_UNITS_RESPONSE_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::storage::stream_track_units::{
         StreamTrackUnitsRequest, StreamTrackUnitsResponse,
     };
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+    use crate::objects::AnyMessage;
     use crate::primitives::{pack_stream_name, pack_track_name, Unit};
-    use crate::utils::get_avro_path;
     use uuid::Uuid;
 
     #[test]
     fn test_load_save_req() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -260,29 +227,15 @@ mod tests {
             500,
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = StreamTrackUnitsRequest::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::StreamTrackUnitsRequest(req), new_req);
     }
 
     #[test]
     fn test_load_save_rep() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -301,23 +254,9 @@ mod tests {
             vec![1, 2, 3],
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = StreamTrackUnitsResponse::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::StreamTrackUnitsResponse(req), new_req);
     }
 }
@@ -1,38 +1,25 @@
 use crate::avro::{
-    Builder, ProtocolMessage, STREAM_TRACKS_REQUEST_SCHEMA, STREAM_TRACKS_RESPONSE_SCHEMA,
+    BuilderImpl, ProtocolMessage, STREAM_TRACKS_REQUEST_SCHEMA, STREAM_TRACKS_RESPONSE_SCHEMA,
     TRACK_INFO_SCHEMA,
 };
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
-use crate::primitives::{
-    track_type_literal_to_track_type, StreamName, TrackInfo, TrackName, TrackType,
-};
-use crate::utils::fill_byte_array;
+use crate::error::Result;
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
+use crate::primitives::{get_track_type_enum, track_type_from_symbol, StreamName, TrackInfo};
+use crate::record::RecordFields;
+use crate::utils::to_byte_array;
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-fn get_track_type_enum(track_type: &TrackType) -> Value {
-    match track_type {
-        TrackType::Video => Value::Enum(0, "VIDEO".into()),
-        TrackType::Meta => Value::Enum(1, "META".into()),
-        TrackType::NotImplemented => panic!("Not supported track type"),
-    }
-}
-
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTracksResponse {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub stream_name: StreamName,
-    #[pyo3(get, set)]
     pub tracks: Vec<TrackInfo>,
 }
 
-#[pymethods]
 impl StreamTracksResponse {
-    #[new]
     pub fn new(request_id: i64, stream_name: StreamName, tracks: Vec<TrackInfo>) -> Self {
         StreamTracksResponse {
             request_id,
@@ -40,6 +27,15 @@ impl StreamTracksResponse {
             tracks,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTracksResponse {
+    #[new]
+    fn py_new(request_id: i64, stream_name: StreamName, tracks: Vec<TrackInfo>) -> Self {
+        Self::new(request_id, stream_name, tracks)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -49,109 +45,74 @@ impl StreamTracksResponse {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACKS_RESPONSE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl ToProtocolMessage for StreamTracksResponse {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACKS_RESPONSE_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACKS_RESPONSE_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
         obj.put("stream_name", Value::Bytes(self.stream_name.to_vec()));
-        let tracks: Vec<Value> = self
+        let tracks = self
             .tracks
             .iter()
-            .map(|track_info| {
-                let mut r = mb.get_record(TRACK_INFO_SCHEMA);
+            .map(|track_info| -> Result<Value> {
+                let mut r = mb.get_record(TRACK_INFO_SCHEMA)?;
                 r.put("name", Value::Bytes(track_info.track_name.to_vec()));
-                r.put("type", get_track_type_enum(&track_info.track_type));
-                r.into()
+                r.put("type", get_track_type_enum(&track_info.track_type)?);
+                Ok(Value::from(r))
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
         obj.put("tracks", Value::Array(tracks));
 
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACKS_RESPONSE_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            STREAM_TRACKS_RESPONSE_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
+fn track_info_from_avro(fields: &RecordFields) -> Result<TrackInfo> {
+    Ok(TrackInfo {
+        track_name: to_byte_array("name", &fields.get::<Vec<u8>>("name")?)?,
+        track_type: track_type_from_symbol(fields.enum_symbol("type")?)?,
+    })
+}
+
 impl FromProtocolMessage for StreamTracksResponse {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != STREAM_TRACKS_RESPONSE_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::Bytes(stream_name)), (_, Value::Array(tracks))] =>
-                {
-                    let mut sn = StreamName::default();
-                    fill_byte_array(&mut sn, stream_name);
-                    let track_records: Vec<Option<TrackInfo>> = tracks.iter().map(|t| match t {
-                        Value::Record(fields) => {
-                            match fields.as_slice() {
-                                [
-                                (_, Value::Bytes(track_name)),
-                                (_, Value::Enum(_, track_type))
-                                ] => {
-                                    let mut tn = TrackName::default();
-                                    fill_byte_array(&mut tn, track_name);
-                                    Some(TrackInfo {
-                                        track_name: tn,
-                                        track_type: track_type_literal_to_track_type(track_type.as_str()),
-                                    })
-                                }
-                                _ => None
-                            }
-                        }
-                        _ => None
-                    }).collect();
-
-                    let valid_track_records: Vec<_> =
-                        track_records.iter().flatten().copied().collect();
-
-                    if valid_track_records.len() < track_records.len() {
-                        warn!("Not all track info records are parsed well.");
-                        None
-                    } else {
-                        Some(StreamTracksResponse {
-                            request_id: *request_id,
-                            stream_name: sn,
-                            tracks: valid_track_records,
-                        })
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTracksResponse");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACKS_RESPONSE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        let tracks = fields
+            .records("tracks")?
+            .iter()
+            .map(track_info_from_avro)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(StreamTracksResponse {
+            request_id: fields.get("request_id")?,
+            stream_name: to_byte_array("stream_name", &fields.get::<Vec<u8>>("stream_name")?)?,
+            tracks,
+        })
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct StreamTracksRequest {
-    #[pyo3(get, set)]
     pub request_id: i64,
-    #[pyo3(get, set)]
     pub topic: String,
-    #[pyo3(get, set)]
     pub stream_name: StreamName,
 }
 
-#[pymethods]
 impl StreamTracksRequest {
-    #[new]
     pub fn new(request_id: i64, topic: String, stream_name: StreamName) -> Self {
         StreamTracksRequest {
             request_id,
@@ -159,6 +120,15 @@ impl StreamTracksRequest {
             stream_name,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl StreamTracksRequest {
+    #[new]
+    fn py_new(request_id: i64, topic: String, stream_name: StreamName) -> Self {
+        Self::new(request_id, topic, stream_name)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -168,99 +138,70 @@ impl StreamTracksRequest {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = STREAM_TRACKS_REQUEST_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl ToProtocolMessage for StreamTracksRequest {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(STREAM_TRACKS_REQUEST_SCHEMA);
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(STREAM_TRACKS_REQUEST_SCHEMA)?;
         obj.put("request_id", Value::Long(self.request_id));
         obj.put("topic", Value::String(self.topic.clone()));
         obj.put("stream_name", Value::Bytes(self.stream_name.to_vec()));
-        Some(ProtocolMessage {
-            schema: String::from(STREAM_TRACKS_REQUEST_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            STREAM_TRACKS_REQUEST_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 impl FromProtocolMessage for StreamTracksRequest {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != STREAM_TRACKS_REQUEST_SCHEMA {
-            return None;
-        }
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Long(request_id)), (_, Value::String(topic)), (_, Value::Bytes(stream_name))] =>
-                {
-                    let mut sn = StreamName::default();
-                    fill_byte_array(&mut sn, stream_name);
-                    Some(StreamTracksRequest {
-                        request_id: *request_id,
-                        topic: topic.clone(),
-                        stream_name: sn,
-                    })
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to StreamTracksRequest");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        check_schema(message, STREAM_TRACKS_REQUEST_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(StreamTracksRequest {
+            request_id: fields.get("request_id")?,
+            topic: fields.get("topic")?,
+            stream_name: to_byte_array("stream_name", &fields.get::<Vec<u8>>("stream_name")?)?,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::storage::stream_tracks::{
         StreamTracksRequest, StreamTracksResponse,
     };
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+    use crate::objects::{AnyMessage, FromProtocolMessage, ToProtocolMessage};
     use crate::primitives::{pack_stream_name, pack_track_name, TrackInfo, TrackType};
-    use crate::utils::get_avro_path;
+    use serde_json::json;
     use uuid::Uuid;
 
     #[test]
     fn test_load_save_req() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
         let stream_name = pack_stream_name(&stream_uuid);
 
         let req = StreamTracksRequest::new(0, String::from("test"), stream_name);
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = StreamTracksRequest::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
-
-        let new_req = new_req_opt.unwrap();
-
-        assert_eq!(req, new_req);
+        assert_eq!(AnyMessage::StreamTracksRequest(req), new_req);
     }
 
     #[test]
     fn test_load_save_rep() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
         let stream_name = pack_stream_name(&stream_uuid);
@@ -272,23 +213,48 @@ mod tests {
             vec![TrackInfo::new(TrackType::Video, track_name)],
         );
 
-        let rep_envelope_opt = rep.save(&mb);
-        assert!(rep_envelope_opt.is_some());
+        let rep_serialized = codec.encode(&rep).unwrap();
+        let new_rep = codec.decode(&rep_serialized).unwrap();
 
-        let rep_envelope = rep_envelope_opt.unwrap();
-        let rep_serialized = mb.save_from_avro(rep_envelope);
-
-        let rep_envelope_opt = mb.load_to_avro(rep_serialized);
-        assert!(rep_envelope_opt.is_some());
+        assert_eq!(AnyMessage::StreamTracksResponse(rep), new_rep);
+    }
 
-        let rep_envelope = rep_envelope_opt.unwrap();
+    #[test]
+    fn test_json() {
+        let codec = Codec::default();
 
-        let new_rep_opt = StreamTracksResponse::load(&rep_envelope);
+        let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
+        let rep = StreamTracksResponse::new(
+            7,
+            pack_stream_name(&stream_uuid),
+            vec![TrackInfo::new(
+                TrackType::Video,
+                pack_track_name("test").unwrap(),
+            )],
+        );
 
-        assert!(new_rep_opt.is_some());
+        let json = rep.to_json(codec.builder()).unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(document["object"]["request_id"], json!(7));
+        assert_eq!(
+            document["object"]["stream_name"],
+            json!("fa807469-fbb3-4f63-b1a9-f63fbbf90f41")
+        );
+        assert_eq!(document["object"]["tracks"][0]["name"], json!("test"));
+        assert_eq!(
+            StreamTracksResponse::from_json(codec.builder(), &json),
+            Ok(rep.clone())
+        );
 
-        let new_rep = new_rep_opt.unwrap();
+        let bytes = codec.builder().json_to_bytes(&json).unwrap();
+        assert_eq!(codec.builder().bytes_to_json(&bytes).unwrap(), json);
+        assert_eq!(
+            codec.decode(&bytes).unwrap(),
+            AnyMessage::StreamTracksResponse(rep.clone())
+        );
 
-        assert_eq!(rep, new_rep);
+        let bytes = codec.encode(&rep).unwrap();
+        let json = codec.builder().bytes_to_json(&bytes).unwrap();
+        assert_eq!(codec.builder().json_to_bytes(&json).unwrap(), bytes);
     }
 }
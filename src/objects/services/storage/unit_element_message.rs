@@ -1,30 +1,25 @@
-use crate::avro::{Builder, ProtocolMessage, UNIT_ELEMENT_MESSAGE_SCHEMA};
-use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+use crate::avro::{BuilderImpl, ProtocolMessage, UNIT_ELEMENT_MESSAGE_SCHEMA};
+use crate::error::Result;
+use crate::objects::{check_schema, FromProtocolMessage, ToProtocolMessage};
 use crate::primitives::{ElementType, Unit};
-use crate::utils::value_to_string;
+use crate::record::RecordFields;
+use crate::utils::{checked_cast, gen_hash_map};
 use avro_rs::types::Value;
-use log::warn;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct UnitElementMessage {
-    #[pyo3(get, set)]
     pub stream_unit: Unit,
-    #[pyo3(get, set)]
     pub element: ElementType,
-    #[pyo3(get, set)]
     pub value: Vec<u8>,
-    #[pyo3(get, set)]
     pub attributes: HashMap<String, String>,
-    #[pyo3(get, set)]
     pub last: bool,
 }
 
-#[pymethods]
 impl UnitElementMessage {
-    #[new]
     pub fn new(
         stream_unit: Unit,
         element: ElementType,
@@ -40,6 +35,21 @@ impl UnitElementMessage {
             last,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl UnitElementMessage {
+    #[new]
+    fn py_new(
+        stream_unit: Unit,
+        element: ElementType,
+        value: Vec<u8>,
+        attributes: HashMap<String, String>,
+        last: bool,
+    ) -> Self {
+        Self::new(stream_unit, element, value, attributes, last)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -49,103 +59,59 @@ impl UnitElementMessage {
         self.__repr__()
     }
 
+    #[classattr]
+    const SCHEMA: &'static str = UNIT_ELEMENT_MESSAGE_SCHEMA;
+
     #[classattr]
     const __hash__: Option<Py<PyAny>> = None;
 }
 
 impl FromProtocolMessage for UnitElementMessage {
-    fn load(message: &ProtocolMessage) -> Option<Self>
+    fn load(message: &ProtocolMessage) -> Result<Self>
     where
         Self: Sized,
     {
-        if message.schema != UNIT_ELEMENT_MESSAGE_SCHEMA {
-            return None;
-        }
-
-        match &message.object {
-            Value::Record(fields) => match fields.as_slice() {
-                [(_, Value::Record(stream_unit_fields)), (_, Value::Long(element)), (_, Value::Bytes(value)), (_, Value::Map(attributes)), (_, Value::Boolean(last))] => {
-                    match stream_unit_fields.as_slice() {
-                        [(_, Value::Bytes(stream_name)), (_, Value::Bytes(track_name)), (_, Value::Enum(_index, track_type)), (_, Value::Long(unit))] => {
-                            Some(UnitElementMessage {
-                                stream_unit: Unit::new(
-                                    stream_name.clone(),
-                                    track_name.clone(),
-                                    track_type.clone(),
-                                    *unit,
-                                ),
-                                element: *element as i16,
-                                value: value.clone(),
-                                attributes: attributes
-                                    .iter()
-                                    .map(|x| {
-                                        (
-                                            x.0.clone(),
-                                            value_to_string(x.1)
-                                                .or_else(|| Some(String::from("")))
-                                                .unwrap(),
-                                        )
-                                    })
-                                    .collect(),
-                                last: *last,
-                            })
-                        }
-                        _ => {
-                            warn!("Unable to match AVRO Record to Unit");
-                            None
-                        }
-                    }
-                }
-                _ => {
-                    warn!("Unable to match AVRO Record to to UnitElementMessage");
-                    None
-                }
-            },
-            _ => {
-                warn!("Unable to match AVRO Record.");
-                None
-            }
-        }
+        check_schema(message, UNIT_ELEMENT_MESSAGE_SCHEMA)?;
+        let fields = RecordFields::from_message(message)?;
+        Ok(UnitElementMessage {
+            stream_unit: Unit::from_avro_record(&fields.record("stream_unit")?)?,
+            element: checked_cast("element", fields.get::<i64>("element")?)?,
+            value: fields.get("value")?,
+            attributes: fields.get_or_default("attributes")?,
+            last: fields.get("last")?,
+        })
     }
 }
 
 impl ToProtocolMessage for UnitElementMessage {
-    fn save(&self, mb: &Builder) -> Option<ProtocolMessage> {
-        let mut obj = mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA);
-        obj.put("stream_unit", self.stream_unit.to_avro_record());
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let mut obj = mb.get_record(UNIT_ELEMENT_MESSAGE_SCHEMA)?;
+        obj.put("stream_unit", self.stream_unit.to_avro_record()?);
         obj.put("element", Value::Long(self.element.into()));
         obj.put("value", Value::Bytes(self.value.clone()));
-        obj.put(
-            "attributes",
-            Value::Map(
-                self.attributes
-                    .iter()
-                    .map(|x| (x.0.clone(), Value::String(x.1.clone())))
-                    .collect(),
-            ),
-        );
+        obj.put("attributes", gen_hash_map(&self.attributes));
         obj.put("last", Value::Boolean(self.last));
 
-        Some(ProtocolMessage {
-            schema: String::from(UNIT_ELEMENT_MESSAGE_SCHEMA),
-            object: Value::from(obj),
-        })
+        Ok(ProtocolMessage::new(
+            UNIT_ELEMENT_MESSAGE_SCHEMA,
+            Value::from(obj),
+        ))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::avro::Builder;
+    use crate::codec::Codec;
     use crate::objects::services::storage::unit_element_message::UnitElementMessage;
-    use crate::objects::{FromProtocolMessage, ToProtocolMessage};
+    use crate::objects::{AnyMessage, FromProtocolMessage, ToProtocolMessage};
     use crate::primitives::{pack_stream_name, pack_track_name, Unit};
-    use crate::utils::get_avro_path;
+    use avro_rs::types::Value;
     use std::collections::HashMap;
     use uuid::Uuid;
 
     #[test]
     fn test_load_save_req() {
-        let mb = Builder::new(get_avro_path().as_str());
+        let codec = Codec::default();
 
         let track_name = pack_track_name(&String::from("test")).unwrap();
         let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
@@ -164,23 +130,33 @@ mod tests {
             true,
         );
 
-        let req_envelope_opt = req.save(&mb);
-        assert!(req_envelope_opt.is_some());
-
-        let req_envelope = req_envelope_opt.unwrap();
-        let req_serialized = mb.save_from_avro(req_envelope);
-
-        let req_envelope_opt = mb.load_to_avro(req_serialized);
-        assert!(req_envelope_opt.is_some());
+        let req_serialized = codec.encode(&req).unwrap();
+        let new_req = codec.decode(&req_serialized).unwrap();
 
-        let req_envelope = req_envelope_opt.unwrap();
-
-        let new_req_opt = UnitElementMessage::load(&req_envelope);
-
-        assert!(new_req_opt.is_some());
+        assert_eq!(AnyMessage::UnitElementMessage(req), new_req);
+    }
 
-        let new_req = new_req_opt.unwrap();
+    #[test]
+    fn test_load_without_attributes() {
+        let codec = Codec::default();
+        let stream_uuid = Uuid::parse_str("fa807469-fbb3-4f63-b1a9-f63fbbf90f41").unwrap();
+        let req = UnitElementMessage::new(
+            Unit::new(
+                pack_stream_name(&stream_uuid).to_vec(),
+                pack_track_name(&String::from("test")).unwrap().to_vec(),
+                String::from("VIDEO"),
+                3,
+            ),
+            2,
+            vec![0, 1],
+            HashMap::default(),
+            true,
+        );
 
-        assert_eq!(req, new_req);
+        let mut message = req.save(codec.builder()).unwrap();
+        if let Value::Record(fields) = &mut message.object {
+            fields.retain(|(name, _)| name != "attributes");
+        }
+        assert_eq!(UnitElementMessage::load(&message), Ok(req));
     }
 }
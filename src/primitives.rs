@@ -1,5 +1,8 @@
-use crate::utils::fill_byte_array;
+use crate::error::{ProtocolError, Result};
+use crate::record::RecordFields;
+use crate::utils::{fill_byte_array, to_byte_array};
 use avro_rs::types::Value;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -12,35 +15,35 @@ pub type StreamName = [u8; STREAM_NAME_MAX_LENGTH];
 pub type TrackName = [u8; TRACK_NAME_MAX_LENGTH];
 pub type ElementType = i16;
 
-#[derive(Debug, Clone, PartialEq, Copy, Eq, Hash)]
-#[pyclass]
+#[derive(Debug, Default, Clone, PartialEq, Copy, Eq, Hash)]
+#[cfg_attr(feature = "python", pyclass)]
 pub enum TrackType {
+    #[default]
     Video,
     Meta,
     NotImplemented,
 }
 
-impl Default for TrackType {
-    fn default() -> Self {
-        TrackType::Video
-    }
-}
-
 #[derive(Debug, Default, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct Payload {
-    #[pyo3(get, set)]
     pub data: Vec<u8>,
-    #[pyo3(get, set)]
     pub attributes: HashMap<String, String>,
 }
 
-#[pymethods]
 impl Payload {
-    #[new]
     pub fn new(data: Vec<u8>, attributes: HashMap<String, String>) -> Self {
         Payload { data, attributes }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Payload {
+    #[new]
+    fn py_new(data: Vec<u8>, attributes: HashMap<String, String>) -> Self {
+        Self::new(data, attributes)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -55,23 +58,28 @@ impl Payload {
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Copy)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct TrackInfo {
-    #[pyo3(get, set)]
     pub track_type: TrackType,
-    #[pyo3(get, set)]
     pub track_name: TrackName,
 }
 
-#[pymethods]
 impl TrackInfo {
-    #[new]
     pub fn new(track_type: TrackType, track_name: TrackName) -> Self {
         TrackInfo {
             track_type,
             track_name,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl TrackInfo {
+    #[new]
+    fn py_new(track_type: TrackType, track_name: TrackName) -> Self {
+        Self::new(track_type, track_name)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -109,15 +117,11 @@ pub fn pack_track_name(track_name: &str) -> std::result::Result<TrackName, Strin
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass(get_all, set_all))]
 pub struct Unit {
-    #[pyo3(get, set)]
     pub stream_name: StreamName,
-    #[pyo3(get, set)]
     pub track_name: TrackName,
-    #[pyo3(get, set)]
     pub track_type: TrackType,
-    #[pyo3(get, set)]
     pub unit: i64,
 }
 
@@ -129,9 +133,17 @@ pub fn track_type_literal_to_track_type(literal: &str) -> TrackType {
     }
 }
 
-#[pymethods]
+pub fn track_type_from_symbol(symbol: &str) -> Result<TrackType> {
+    match track_type_literal_to_track_type(symbol) {
+        TrackType::NotImplemented => Err(ProtocolError::UnknownEnumSymbol {
+            enum_name: String::from("TrackType"),
+            symbol: String::from(symbol),
+        }),
+        track_type => Ok(track_type),
+    }
+}
+
 impl Unit {
-    #[new]
     pub fn new(stream_name: Vec<u8>, track_name: Vec<u8>, track_type: String, unit: i64) -> Unit {
         let mut b_stream_name: StreamName = StreamName::default();
         let mut b_track_name: TrackName = TrackName::default();
@@ -146,6 +158,15 @@ impl Unit {
             unit,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Unit {
+    #[new]
+    fn py_new(stream_name: Vec<u8>, track_name: Vec<u8>, track_type: String, unit: i64) -> Self {
+        Self::new(stream_name, track_name, track_type, unit)
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self)
@@ -159,25 +180,38 @@ impl Unit {
     const __hash__: Option<Py<PyAny>> = None;
 }
 
-fn get_track_type_enum(track_type: &TrackType) -> Value {
+pub fn get_track_type_enum(track_type: &TrackType) -> Result<Value> {
     match track_type {
-        TrackType::Video => Value::Enum(0, "VIDEO".into()),
-        TrackType::Meta => Value::Enum(1, "META".into()),
-        TrackType::NotImplemented => panic!("Not supported track type"),
+        TrackType::Video => Ok(Value::Enum(0, "VIDEO".into())),
+        TrackType::Meta => Ok(Value::Enum(1, "META".into())),
+        TrackType::NotImplemented => Err(ProtocolError::UnknownEnumSymbol {
+            enum_name: String::from("TrackType"),
+            symbol: String::from("NotImplemented"),
+        }),
     }
 }
 
 impl Unit {
-    pub fn to_avro_record(&self) -> Value {
-        Value::Record(vec![
+    pub fn to_avro_record(&self) -> Result<Value> {
+        Ok(Value::Record(vec![
             (
                 "stream_name".into(),
                 Value::Bytes(self.stream_name.to_vec()),
             ),
             ("track_name".into(), Value::Bytes(self.track_name.to_vec())),
-            ("track_type".into(), get_track_type_enum(&self.track_type)),
+            ("track_type".into(), get_track_type_enum(&self.track_type)?),
             ("unit".into(), Value::Long(self.unit)),
-        ])
+        ]))
+    }
+
+    /// Decodes the `stream_unit` record of a message.
+    pub fn from_avro_record(fields: &RecordFields) -> Result<Unit> {
+        Ok(Unit {
+            stream_name: to_byte_array("stream_name", &fields.get::<Vec<u8>>("stream_name")?)?,
+            track_name: to_byte_array("track_name", &fields.get::<Vec<u8>>("track_name")?)?,
+            track_type: track_type_from_symbol(fields.enum_symbol("track_type")?)?,
+            unit: fields.get("unit")?,
+        })
     }
 }
 
@@ -189,27 +223,40 @@ pub enum NotifyTypeImpl {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-#[pyclass]
+#[cfg_attr(feature = "python", pyclass)]
 pub struct NotifyType {
     pub obj: NotifyTypeImpl,
 }
 
 #[allow(clippy::new_without_default)]
-#[pymethods]
 impl NotifyType {
-    #[staticmethod]
     pub fn ready(element: ElementType) -> Self {
         NotifyType {
             obj: NotifyTypeImpl::Ready(element),
         }
     }
 
-    #[staticmethod]
     pub fn new() -> Self {
         NotifyType {
             obj: NotifyTypeImpl::New,
         }
     }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl NotifyType {
+    #[staticmethod]
+    #[pyo3(name = "ready")]
+    fn py_ready(element: ElementType) -> Self {
+        Self::ready(element)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "new")]
+    fn py_new() -> Self {
+        Self::new()
+    }
 
     fn __repr__(&self) -> String {
         format!("{:?}", self.obj)
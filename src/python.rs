@@ -0,0 +1,187 @@
+use crate::avro::{BuilderImpl, ProtocolMessage};
+use crate::error::{ProtocolError, Result};
+use crate::objects::{AnyMessage, ToProtocolMessage};
+use avro_rs::schema::Schema;
+use avro_rs::types::Value;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Instance of a Python class registered with `Builder.register_class`.
+///
+/// The class provides the `SCHEMA` attribute, the `to_avro()` method returning the record as
+/// a dict, and the `from_avro(dict)` classmethod.
+#[derive(Debug)]
+pub struct PyCustomMessage {
+    pub schema: String,
+    pub object: PyObject,
+}
+
+impl PartialEq for PyCustomMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.schema == other.schema && self.object.is(&other.object)
+    }
+}
+
+impl ToProtocolMessage for PyCustomMessage {
+    fn save(&self, mb: &BuilderImpl) -> Result<ProtocolMessage> {
+        let schema = mb.get_known_schema(&self.schema)?;
+        let object = Python::with_gil(|py| {
+            let record = self.object.call_method0(py, "to_avro")?;
+            py_to_value(record.as_ref(py), schema)
+        })
+        .map_err(|e| ProtocolError::Encode(format!("{} ({})", e, self.schema)))?;
+        Ok(ProtocolMessage::new(&self.schema, object))
+    }
+}
+
+/// Wraps the object of a registered class; `schema` is the full name its `SCHEMA` resolves to.
+pub(crate) fn extract_custom(ob: &PyAny, schema: &str) -> PyResult<AnyMessage> {
+    Ok(AnyMessage::Custom {
+        schema: String::from(schema),
+        message: Arc::new(PyCustomMessage {
+            schema: String::from(schema),
+            object: ob.into(),
+        }),
+    })
+}
+
+/// Converts the Python object into the Avro value of the schema.
+pub fn py_to_value(ob: &PyAny, schema: &Schema) -> PyResult<Value> {
+    Ok(match schema {
+        Schema::Null if ob.is_none() => Value::Null,
+        Schema::Boolean => Value::Boolean(ob.extract()?),
+        Schema::Int => Value::Int(ob.extract()?),
+        Schema::Long => Value::Long(ob.extract()?),
+        Schema::Float => Value::Float(ob.extract()?),
+        Schema::Double => Value::Double(ob.extract()?),
+        Schema::Bytes => Value::Bytes(ob.extract()?),
+        Schema::String => Value::String(ob.extract()?),
+        Schema::Fixed { size, .. } => {
+            let bytes: Vec<u8> = ob.extract()?;
+            if bytes.len() != *size {
+                return Err(PyValueError::new_err(format!(
+                    "Fixed value must be {} bytes long, got {}",
+                    size,
+                    bytes.len()
+                )));
+            }
+            Value::Fixed(*size, bytes)
+        }
+        Schema::Enum { symbols, .. } => {
+            let symbol: String = ob.extract()?;
+            let index = symbols
+                .iter()
+                .position(|s| *s == symbol)
+                .ok_or_else(|| PyValueError::new_err(format!("Unknown enum symbol {}", symbol)))?;
+            Value::Enum(index as i32, symbol)
+        }
+        Schema::Array(items) => Value::Array(
+            ob.iter()?
+                .map(|item| py_to_value(item?, items))
+                .collect::<PyResult<_>>()?,
+        ),
+        Schema::Map(values) => Value::Map(
+            ob.downcast::<PyDict>()?
+                .iter()
+                .map(|(k, v)| Ok((k.extract()?, py_to_value(v, values)?)))
+                .collect::<PyResult<HashMap<_, _>>>()?,
+        ),
+        Schema::Union(union) => {
+            match union
+                .variants()
+                .iter()
+                .find_map(|variant| py_to_value(ob, variant).ok())
+            {
+                Some(value) => Value::Union(Box::new(value)),
+                None => {
+                    return Err(PyTypeError::new_err(format!(
+                        "No union variant matches {}",
+                        ob.repr()?
+                    )))
+                }
+            }
+        }
+        Schema::Record { fields, .. } => {
+            let dict = ob.downcast::<PyDict>()?;
+            Value::Record(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let value = match dict.get_item(&field.name) {
+                            Some(item) => py_to_value(item, &field.schema)?,
+                            None => match &field.default {
+                                Some(default) => Value::from(default.clone())
+                                    .resolve(&field.schema)
+                                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                                None => {
+                                    return Err(PyValueError::new_err(format!(
+                                        "Missing field {}",
+                                        field.name
+                                    )))
+                                }
+                            },
+                        };
+                        Ok((field.name.clone(), value))
+                    })
+                    .collect::<PyResult<_>>()?,
+            )
+        }
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "Unable to convert {} to {:?}",
+                ob.repr()?,
+                schema
+            )))
+        }
+    })
+}
+
+/// Converts the Avro value into plain Python objects; records and maps become dicts.
+pub fn value_to_py(py: Python, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Boolean(b) => b.into_py(py),
+        Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => i.into_py(py),
+        Value::Long(l)
+        | Value::TimeMicros(l)
+        | Value::TimestampMillis(l)
+        | Value::TimestampMicros(l) => l.into_py(py),
+        Value::Float(f) => f.into_py(py),
+        Value::Double(d) => d.into_py(py),
+        Value::Bytes(b) | Value::Fixed(_, b) => PyBytes::new(py, b).into(),
+        Value::String(s) | Value::Enum(_, s) => s.into_py(py),
+        Value::Union(v) => value_to_py(py, v)?,
+        Value::Array(items) => PyList::new(
+            py,
+            items
+                .iter()
+                .map(|item| value_to_py(py, item))
+                .collect::<PyResult<Vec<_>>>()?,
+        )
+        .into(),
+        Value::Map(values) => {
+            let dict = PyDict::new(py);
+            for (k, v) in values {
+                dict.set_item(k, value_to_py(py, v)?)?;
+            }
+            dict.into()
+        }
+        Value::Record(fields) => {
+            let dict = PyDict::new(py);
+            for (k, v) in fields {
+                dict.set_item(k, value_to_py(py, v)?)?;
+            }
+            dict.into()
+        }
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "Unable to convert {:?} to a Python object",
+                value
+            )))
+        }
+    })
+}
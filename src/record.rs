@@ -0,0 +1,387 @@
+use crate::avro::ProtocolMessage;
+use crate::error::{ProtocolError, Result};
+use crate::json::json_to_value;
+use avro_rs::schema::{RecordField, Schema};
+use avro_rs::types::Value;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Plain Rust value stored in a record field.
+pub trait FromAvroValue: Sized {
+    fn from_avro_value(value: &Value) -> Option<Self>;
+}
+
+impl FromAvroValue for bool {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for i32 {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for i64 {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Long(l) => Some(*l),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for f32 {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for f64 {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for String {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for Vec<u8> {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bytes(b) | Value::Fixed(_, b) => Some(b.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromAvroValue for HashMap<String, String> {
+    fn from_avro_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Map(map) => map
+                .iter()
+                .map(|(k, v)| String::from_avro_value(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Fields of a decoded Avro record, looked up by name.
+///
+/// The field order does not matter and fields unknown to the decoder are ignored, so the
+/// schemas may evolve without breaking the decoders. A field absent from the writer's data
+/// but declared with a default in the reader schema is filled in by the Avro schema
+/// resolution before it gets here; a field missing from the value otherwise is reported as
+/// `MissingField`, unless the decoder asks for it with `get_or_default`: it then takes the
+/// default of the record schema given with `with_schema`, or the empty value of its type when
+/// the schema is not known.
+pub struct RecordFields<'a> {
+    schema: &'a str,
+    path: String,
+    fields: &'a [(String, Value)],
+    /// Fields of the record schema, `None` if the schema is not known.
+    record: Option<&'a [RecordField]>,
+}
+
+impl<'a> RecordFields<'a> {
+    /// Fields of the record `value` of the message with the schema `schema`.
+    pub fn new(schema: &'a str, value: &'a Value) -> Result<RecordFields<'a>> {
+        match value {
+            Value::Record(fields) => Ok(RecordFields {
+                schema,
+                path: String::new(),
+                fields,
+                record: None,
+            }),
+            _ => Err(ProtocolError::RecordMismatch(String::from(schema))),
+        }
+    }
+
+    /// Fields of the record `value` of the record schema `record`, which provides the
+    /// defaults of the missing fields.
+    pub fn with_schema(
+        schema: &'a str,
+        value: &'a Value,
+        record: &'a Schema,
+    ) -> Result<RecordFields<'a>> {
+        Ok(RecordFields {
+            record: record_fields(record),
+            ..Self::new(schema, value)?
+        })
+    }
+
+    pub fn from_message(message: &'a ProtocolMessage) -> Result<RecordFields<'a>> {
+        Self::new(&message.schema, &message.object)
+    }
+
+    /// Raw value of the field with unions unwrapped.
+    pub fn value(&self, name: &str) -> Result<&'a Value> {
+        self.find(name).ok_or_else(|| ProtocolError::MissingField {
+            schema: String::from(self.schema),
+            field: self.field_path(name),
+        })
+    }
+
+    /// Raw value of the field, `None` if it is absent or null.
+    pub fn optional_value(&self, name: &str) -> Option<&'a Value> {
+        self.find(name).filter(|v| !matches!(v, Value::Null))
+    }
+
+    pub fn get<T: FromAvroValue>(&self, name: &str) -> Result<T> {
+        T::from_avro_value(self.value(name)?).ok_or_else(|| self.mismatch(name))
+    }
+
+    /// The value of the field, `None` if it is absent or null.
+    pub fn get_optional<T: FromAvroValue>(&self, name: &str) -> Result<Option<T>> {
+        self.optional_value(name)
+            .map(|value| T::from_avro_value(value).ok_or_else(|| self.mismatch(name)))
+            .transpose()
+    }
+
+    /// The value of the field, or its default in the record schema if it is absent. A null,
+    /// or an absent field of a record whose schema is not known, stands for the empty value of
+    /// `T`, say an empty map.
+    pub fn get_or_default<T: FromAvroValue + Default>(&self, name: &str) -> Result<T> {
+        let value = match self.find(name) {
+            Some(value) => Cow::Borrowed(value),
+            None if self.record.is_none() => return Ok(T::default()),
+            None => Cow::Owned(self.schema_default(name)?),
+        };
+        match value.as_ref() {
+            Value::Null => Ok(T::default()),
+            value => T::from_avro_value(value).ok_or_else(|| self.mismatch(name)),
+        }
+    }
+
+    /// The default the record schema declares for the field.
+    fn schema_default(&self, name: &str) -> Result<Value> {
+        let field = self
+            .record
+            .and_then(|fields| fields.iter().find(|f| f.name == name));
+        let (default, schema) = match field {
+            Some(RecordField {
+                default: Some(default),
+                schema,
+                ..
+            }) => (default, schema),
+            _ => {
+                return Err(ProtocolError::MissingField {
+                    schema: String::from(self.schema),
+                    field: self.field_path(name),
+                })
+            }
+        };
+        // The default of a union is a value of its first branch.
+        let schema = match schema {
+            Schema::Union(union) => union.variants().first().unwrap_or(schema),
+            _ => schema,
+        };
+        json_to_value(default, schema).map_err(|_| self.mismatch(name))
+    }
+
+    pub fn enum_symbol(&self, name: &str) -> Result<&'a str> {
+        match self.value(name)? {
+            Value::Enum(_, symbol) => Ok(symbol),
+            _ => Err(self.mismatch(name)),
+        }
+    }
+
+    pub fn array<T: FromAvroValue>(&self, name: &str) -> Result<Vec<T>> {
+        self.items(name)?
+            .iter()
+            .map(|item| T::from_avro_value(item).ok_or_else(|| self.mismatch(name)))
+            .collect()
+    }
+
+    /// Fields of the nested record.
+    pub fn record(&self, name: &str) -> Result<RecordFields<'a>> {
+        let record = self.field_schema(name).and_then(record_fields);
+        self.nested(name, self.value(name)?, record)
+    }
+
+    /// Fields of every record of the array.
+    pub fn records(&self, name: &str) -> Result<Vec<RecordFields<'a>>> {
+        let record = match self.field_schema(name) {
+            Some(Schema::Array(items)) => record_fields(items),
+            _ => None,
+        };
+        self.items(name)?
+            .iter()
+            .map(|item| self.nested(name, item, record))
+            .collect()
+    }
+
+    pub fn mismatch(&self, name: &str) -> ProtocolError {
+        ProtocolError::FieldMismatch {
+            schema: String::from(self.schema),
+            field: self.field_path(name),
+        }
+    }
+
+    fn field_schema(&self, name: &str) -> Option<&'a Schema> {
+        let fields = self.record?;
+        fields.iter().find(|f| f.name == name).map(|f| &f.schema)
+    }
+
+    fn find(&self, name: &str) -> Option<&'a Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| unwrap_union(value))
+    }
+
+    fn items(&self, name: &str) -> Result<&'a [Value]> {
+        match self.value(name)? {
+            Value::Array(items) => Ok(items),
+            _ => Err(self.mismatch(name)),
+        }
+    }
+
+    fn nested(
+        &self,
+        name: &str,
+        value: &'a Value,
+        record: Option<&'a [RecordField]>,
+    ) -> Result<RecordFields<'a>> {
+        match value {
+            Value::Record(fields) => Ok(RecordFields {
+                schema: self.schema,
+                path: self.field_path(name),
+                fields,
+                record,
+            }),
+            _ => Err(self.mismatch(name)),
+        }
+    }
+
+    fn field_path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            String::from(name)
+        } else {
+            format!("{}.{}", self.path, name)
+        }
+    }
+}
+
+/// Fields of the record schema, or of the record branch of an optional record.
+fn record_fields(schema: &Schema) -> Option<&[RecordField]> {
+    match schema {
+        Schema::Record { fields, .. } => Some(fields),
+        Schema::Union(union) => union.variants().iter().find_map(record_fields),
+        _ => None,
+    }
+}
+
+fn unwrap_union(value: &Value) -> &Value {
+    match value {
+        Value::Union(inner) => unwrap_union(inner),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ProtocolError;
+    use crate::record::RecordFields;
+    use avro_rs::types::Value;
+    use avro_rs::Schema;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_lookup_by_name() {
+        let value = Value::Record(vec![
+            ("extra".into(), Value::Boolean(true)),
+            (
+                "topic".into(),
+                Value::Union(Box::new(Value::String("t".into()))),
+            ),
+            (
+                "unit".into(),
+                Value::Record(vec![("id".into(), Value::Long(7))]),
+            ),
+            ("note".into(), Value::Union(Box::new(Value::Null))),
+        ]);
+        let fields = RecordFields::new("schema", &value).unwrap();
+
+        assert_eq!(fields.get::<String>("topic").unwrap(), "t");
+        assert_eq!(fields.record("unit").unwrap().get::<i64>("id").unwrap(), 7);
+        assert_eq!(fields.get_or_default::<String>("note").unwrap(), "");
+        assert_eq!(
+            fields.get_or_default::<HashMap<String, String>>("attributes"),
+            Ok(HashMap::default())
+        );
+        assert_eq!(
+            fields.get::<i64>("topic"),
+            Err(ProtocolError::FieldMismatch {
+                schema: "schema".into(),
+                field: "topic".into()
+            })
+        );
+        assert_eq!(
+            fields.record("unit").unwrap().get::<i64>("unit"),
+            Err(ProtocolError::MissingField {
+                schema: "schema".into(),
+                field: "unit.unit".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_schema_defaults() {
+        let schema = Schema::parse_str(
+            r#"{"type": "record", "name": "Sample", "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "priority", "type": "int", "default": 5},
+                {"name": "label", "type": "string", "default": "none"},
+                {"name": "attributes", "type": ["null", {"type": "map", "values": "string"}],
+                 "default": null},
+                {"name": "required", "type": "int"}
+            ]}"#,
+        )
+        .unwrap();
+        let value = Value::Record(vec![("id".into(), Value::Long(1))]);
+        let fields = RecordFields::with_schema("schema", &value, &schema).unwrap();
+
+        assert_eq!(fields.get_or_default::<i32>("priority"), Ok(5));
+        assert_eq!(fields.get_or_default::<String>("label").unwrap(), "none");
+        assert_eq!(
+            fields.get_or_default::<HashMap<String, String>>("attributes"),
+            Ok(HashMap::default())
+        );
+        assert_eq!(
+            fields.get_or_default::<i32>("required"),
+            Err(ProtocolError::MissingField {
+                schema: "schema".into(),
+                field: "required".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_not_a_record() {
+        assert_eq!(
+            RecordFields::new("schema", &Value::Null).err(),
+            Some(ProtocolError::RecordMismatch("schema".into()))
+        );
+    }
+}
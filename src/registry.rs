@@ -0,0 +1,120 @@
+use crate::avro::ProtocolMessage;
+use crate::error::{ProtocolError, Result};
+use crate::objects::{builtin_handlers, AnyMessage, CustomMessage, FromProtocolMessage};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Converts a message of one schema between its Avro and typed forms.
+#[derive(Clone, Copy)]
+pub struct MessageHandler {
+    pub decode: fn(&ProtocolMessage) -> Result<AnyMessage>,
+    /// Extracts the message from the Python object whose `SCHEMA` resolves to the handler,
+    /// given the full name the schema is registered under.
+    #[cfg(feature = "python")]
+    pub extract: Option<fn(&PyAny, &str) -> PyResult<AnyMessage>>,
+}
+
+impl MessageHandler {
+    /// Decodes messages of the schema into the custom type `T`.
+    pub fn custom<T>() -> MessageHandler
+    where
+        T: FromProtocolMessage + CustomMessage + 'static,
+    {
+        MessageHandler {
+            decode: decode_custom::<T>,
+            #[cfg(feature = "python")]
+            extract: None,
+        }
+    }
+
+    /// Keeps messages of the schema as undecoded Avro values.
+    pub fn raw() -> MessageHandler {
+        Self::custom::<ProtocolMessage>()
+    }
+}
+
+fn decode_custom<T>(message: &ProtocolMessage) -> Result<AnyMessage>
+where
+    T: FromProtocolMessage + CustomMessage + 'static,
+{
+    Ok(AnyMessage::Custom {
+        schema: message.schema.clone(),
+        message: Arc::new(T::load(message)?),
+    })
+}
+
+/// Message handlers keyed by the schema name carried in the envelope.
+pub struct Registry {
+    handlers: HashMap<String, MessageHandler>,
+}
+
+impl Registry {
+    pub fn empty() -> Registry {
+        Registry {
+            handlers: HashMap::default(),
+        }
+    }
+
+    /// Registers the handler for `schema`, returning the one it replaces.
+    pub fn register(&mut self, schema: &str, handler: MessageHandler) -> Option<MessageHandler> {
+        self.handlers.insert(String::from(schema), handler)
+    }
+
+    pub fn handler(&self, schema: &str) -> Option<&MessageHandler> {
+        self.handlers.get(schema)
+    }
+
+    pub fn schemas(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(|s| s.as_str())
+    }
+
+    pub fn decode(&self, message: &ProtocolMessage) -> Result<AnyMessage> {
+        let handler = self
+            .handler(&message.schema)
+            .ok_or_else(|| ProtocolError::NoDecoder(message.schema.clone()))?;
+        (handler.decode)(message)
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::empty();
+        for (schema, handler) in builtin_handlers() {
+            registry.register(schema, handler);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::BuilderImpl;
+    use crate::codec::Codec;
+    use crate::error::ProtocolError;
+    use crate::objects::services::keep_alive::KeepAliveMessage;
+    use crate::registry::Registry;
+
+    #[test]
+    fn test_builtin_handlers() {
+        let registry = Registry::default();
+        let builder = BuilderImpl::default();
+        for schema in registry.schemas() {
+            assert!(builder.get_schema(schema).is_some(), "{}", schema);
+        }
+        assert_eq!(registry.schemas().count(), 12);
+    }
+
+    #[test]
+    fn test_no_decoder() {
+        let codec = Codec::default();
+        let bytes = codec
+            .encode(&KeepAliveMessage::new(String::from("module")))
+            .unwrap();
+        let message = codec.decode_message(&bytes).unwrap();
+
+        let res = Registry::empty().decode(&message);
+        assert_eq!(res, Err(ProtocolError::NoDecoder(message.schema.clone())));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::avro::BuilderImpl;
+use crate::error::{ProtocolError, Result};
+use crate::record::RecordFields;
+use avro_rs::types::Value;
+use avro_rs::{Reader, Schema, Writer};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use std::io::{Read, Write};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const RECORDED_MESSAGE_SCHEMA: &str = "insight.transport.RecordedMessage";
+
+/// Schema of the entries of a recording.
+pub const RECORDED_MESSAGE_SCHEMA_JSON: &str = r#"{
+    "type": "record",
+    "name": "RecordedMessage",
+    "namespace": "insight.transport",
+    "fields": [
+        {"name": "received_ms", "type": "long"},
+        {"name": "schema", "type": ["null", "string"], "default": null},
+        {"name": "envelope", "type": "bytes"}
+    ]
+}"#;
+
+fn recorded_message_schema() -> &'static Schema {
+    static SCHEMA: OnceLock<Schema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::parse_str(RECORDED_MESSAGE_SCHEMA_JSON).expect("Invalid recording schema")
+    })
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// The envelope as it was received, with the time of receipt.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct RecordedMessage {
+    /// Receive time in milliseconds since the Unix epoch.
+    pub received_ms: i64,
+    /// Schema of the message, `None` if the recorder could not read it from the framing.
+    pub schema: Option<String>,
+    pub envelope: Vec<u8>,
+}
+
+impl RecordedMessage {
+    fn to_avro(&self) -> Value {
+        Value::Record(vec![
+            ("received_ms".into(), Value::Long(self.received_ms)),
+            (
+                "schema".into(),
+                Value::Union(Box::new(
+                    self.schema
+                        .clone()
+                        .map(Value::String)
+                        .unwrap_or(Value::Null),
+                )),
+            ),
+            ("envelope".into(), Value::Bytes(self.envelope.clone())),
+        ])
+    }
+
+    fn from_avro(value: &Value) -> Result<RecordedMessage> {
+        let fields = RecordFields::new(RECORDED_MESSAGE_SCHEMA, value)?;
+        Ok(RecordedMessage {
+            received_ms: fields.get("received_ms")?,
+            schema: fields.get_optional("schema")?,
+            envelope: fields.get("envelope")?,
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl RecordedMessage {
+    fn __repr__(&self) -> String {
+        format!(
+            "RecordedMessage {{ received_ms: {}, schema: {:?}, envelope: {} bytes }}",
+            self.received_ms,
+            self.schema,
+            self.envelope.len()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+}
+
+/// Appends the received envelopes to an Avro object container file.
+pub struct Recorder<W: Write> {
+    writer: Writer<'static, W>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(inner: W) -> Self {
+        Recorder {
+            writer: Writer::new(recorded_message_schema(), inner),
+        }
+    }
+
+    /// Records the envelope as received now; the schema is read from the framing of the
+    /// envelope without decoding it, unreadable envelopes are recorded as well.
+    pub fn record(&mut self, builder: &BuilderImpl, envelope: &[u8]) -> Result<()> {
+        self.record_at(builder, envelope, now_ms())
+    }
+
+    pub fn record_at(
+        &mut self,
+        builder: &BuilderImpl,
+        envelope: &[u8],
+        received_ms: i64,
+    ) -> Result<()> {
+        let message = RecordedMessage {
+            received_ms,
+            schema: builder.peek_schema(envelope).ok(),
+            envelope: envelope.to_vec(),
+        };
+        self.append(&message)
+    }
+
+    pub fn append(&mut self, message: &RecordedMessage) -> Result<()> {
+        self.writer
+            .append(message.to_avro())
+            .map_err(|e| ProtocolError::Stream(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Writes the buffered entries out as a block of the file.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .map_err(|e| ProtocolError::Stream(e.to_string()))?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|e| ProtocolError::Stream(e.to_string()))
+    }
+}
+
+/// Iterates over the recorded envelopes, optionally pacing them as they were received.
+pub struct Player<R: Read> {
+    reader: Reader<'static, R>,
+    /// Playback speed relative to the recording, `None` to play as fast as possible.
+    speed: Option<f64>,
+    /// Receive time of the first message and the moment it was played back.
+    started: Option<(i64, Instant)>,
+}
+
+impl<R: Read> Player<R> {
+    pub fn new(inner: R) -> Result<Self> {
+        let reader = Reader::with_schema(recorded_message_schema(), inner)
+            .map_err(|e| ProtocolError::Stream(e.to_string()))?;
+        Ok(Player {
+            reader,
+            speed: None,
+            started: None,
+        })
+    }
+
+    /// Plays the messages back in real time, `speed` times faster than recorded.
+    pub fn realtime(mut self, speed: f64) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    fn delay(&mut self, received_ms: i64) -> Option<Duration> {
+        let speed = self.speed.filter(|s| *s > 0.0)?;
+        let (first_ms, started) = *self.started.get_or_insert((received_ms, Instant::now()));
+        let offset = (received_ms - first_ms).max(0) as f64 / speed;
+        let due = started + Duration::from_secs_f64(offset / 1000.0);
+        Some(due.saturating_duration_since(Instant::now()))
+    }
+
+    /// The next message without waiting and the time left before it is due.
+    fn next_due(&mut self) -> Option<Result<(RecordedMessage, Option<Duration>)>> {
+        let value = self.reader.next()?;
+        Some(
+            value
+                .map_err(|e| ProtocolError::Stream(e.to_string()))
+                .and_then(|value| RecordedMessage::from_avro(&value))
+                .map(|message| {
+                    let delay = self.delay(message.received_ms);
+                    (message, delay)
+                }),
+        )
+    }
+}
+
+impl<R: Read> Iterator for Player<R> {
+    type Item = Result<RecordedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_due()?.map(|(message, delay)| {
+            if let Some(delay) = delay {
+                thread::sleep(delay);
+            }
+            message
+        }))
+    }
+}
+
+#[cfg(feature = "python")]
+pub use self::python::{PyPlayer, PyRecorder};
+
+#[cfg(feature = "python")]
+mod python {
+    use super::{Player, RecordedMessage, Recorder};
+    use crate::avro::Builder;
+    use crate::error::ProtocolError;
+    use pyo3::exceptions::PyValueError;
+    use pyo3::prelude::*;
+    use std::fs::File;
+    use std::io::{BufReader, BufWriter};
+
+    fn open_error(path: &str, e: std::io::Error) -> PyErr {
+        ProtocolError::Stream(format!("{} ({})", e, path)).into()
+    }
+
+    /// Records the received envelopes to the file at `path`.
+    #[pyclass(name = "Recorder")]
+    pub struct PyRecorder {
+        inner: Option<Recorder<BufWriter<File>>>,
+    }
+
+    impl PyRecorder {
+        fn inner(&mut self) -> PyResult<&mut Recorder<BufWriter<File>>> {
+            self.inner
+                .as_mut()
+                .ok_or_else(|| PyValueError::new_err("The recorder is closed"))
+        }
+    }
+
+    /// Writes the buffered entries out when the recorder is collected without `close()`; the
+    /// failure to do so can only be logged, `close()` raises it.
+    impl Drop for PyRecorder {
+        fn drop(&mut self) {
+            if let Some(recorder) = self.inner.take() {
+                if let Err(e) = recorder.into_inner() {
+                    log::warn!("Failed to flush the recording on drop: {}", e);
+                }
+            }
+        }
+    }
+
+    #[pymethods]
+    impl PyRecorder {
+        #[new]
+        fn new(path: &str) -> PyResult<Self> {
+            let file = File::create(path).map_err(|e| open_error(path, e))?;
+            Ok(PyRecorder {
+                inner: Some(Recorder::new(BufWriter::new(file))),
+            })
+        }
+
+        /// Records the envelope as received now, the builder names its schema.
+        #[pyo3(signature = (builder, envelope, received_ms = None))]
+        fn record(
+            &mut self,
+            builder: &Builder,
+            envelope: Vec<u8>,
+            received_ms: Option<i64>,
+        ) -> PyResult<()> {
+            let builder = builder.builder_impl();
+            let recorder = self.inner()?;
+            match received_ms {
+                Some(received_ms) => recorder.record_at(builder, &envelope, received_ms)?,
+                None => recorder.record(builder, &envelope)?,
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) -> PyResult<()> {
+            Ok(self.inner()?.flush()?)
+        }
+
+        /// Flushes the recording and closes the file, raising the error the flush fails with;
+        /// a recorder dropped unclosed only logs it.
+        fn close(&mut self) -> PyResult<()> {
+            if let Some(recorder) = self.inner.take() {
+                recorder.into_inner()?;
+            }
+            Ok(())
+        }
+
+        fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+            slf
+        }
+
+        fn __exit__(
+            &mut self,
+            _exc_type: &PyAny,
+            _exc_value: &PyAny,
+            _traceback: &PyAny,
+        ) -> PyResult<()> {
+            self.close()
+        }
+    }
+
+    /// Iterates over the recording at `path`, pacing the messages as they were received when
+    /// `speed` is given.
+    #[pyclass(name = "Player")]
+    pub struct PyPlayer {
+        inner: Player<BufReader<File>>,
+    }
+
+    #[pymethods]
+    impl PyPlayer {
+        #[new]
+        #[pyo3(signature = (path, speed = None))]
+        fn new(path: &str, speed: Option<f64>) -> PyResult<Self> {
+            let file = File::open(path).map_err(|e| open_error(path, e))?;
+            let player = Player::new(BufReader::new(file))?;
+            Ok(PyPlayer {
+                inner: match speed {
+                    Some(speed) => player.realtime(speed),
+                    None => player,
+                },
+            })
+        }
+
+        fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+            slf
+        }
+
+        fn __next__(&mut self, py: Python) -> PyResult<Option<RecordedMessage>> {
+            match self.inner.next_due() {
+                Some(Ok((message, delay))) => {
+                    if let Some(delay) = delay {
+                        py.allow_threads(|| std::thread::sleep(delay));
+                    }
+                    Ok(Some(message))
+                }
+                Some(Err(e)) => Err(e.into()),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::KEEPALIVE_MESSAGE_SCHEMA;
+    use crate::codec::Codec;
+    use crate::objects::services::keep_alive::KeepAliveMessage;
+    use crate::replay::{Player, RecordedMessage, Recorder};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_record_and_replay() {
+        let codec = Codec::default();
+        let keep_alive = codec
+            .encode(&KeepAliveMessage::new(String::from("module")))
+            .unwrap();
+        let mut recorder = Recorder::new(Vec::new());
+        recorder
+            .record_at(codec.builder(), &keep_alive, 1000)
+            .unwrap();
+        recorder.record_at(codec.builder(), &[4], 1100).unwrap();
+        // The schema of a payload sealed with a key the recorder lacks is still known.
+        let mut sealing = Codec::default();
+        sealing.encryption_keys_mut().insert("k", &[7; 32]).unwrap();
+        let sealed = sealing
+            .encode_sealed(&KeepAliveMessage::new(String::from("module")), "k")
+            .unwrap();
+        recorder.record_at(codec.builder(), &sealed, 1200).unwrap();
+        let recording = recorder.into_inner().unwrap();
+
+        let messages = Player::new(&recording[..])
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            messages,
+            [
+                RecordedMessage {
+                    received_ms: 1000,
+                    schema: Some(String::from(KEEPALIVE_MESSAGE_SCHEMA)),
+                    envelope: keep_alive
+                },
+                RecordedMessage {
+                    received_ms: 1100,
+                    schema: None,
+                    envelope: vec![4]
+                },
+                RecordedMessage {
+                    received_ms: 1200,
+                    schema: Some(String::from(KEEPALIVE_MESSAGE_SCHEMA)),
+                    envelope: sealed
+                }
+            ]
+        );
+
+        let started = Instant::now();
+        let player = Player::new(&recording[..]).unwrap().realtime(2.0);
+        assert_eq!(player.count(), 3);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+    }
+}
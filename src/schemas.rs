@@ -0,0 +1,70 @@
+/// Bundles the `<full name>.avsc` files of the `API` submodule into the binary at build time,
+/// listing each one along with the directory it is stored in and its full name.
+macro_rules! embed_schemas {
+    ($(($dir:literal, $name:literal)),* $(,)?) => {
+        /// The directory, full name and source of the built-in schemas, in the order they are
+        /// parsed; `BuilderImpl::schema_files()` lists the same files.
+        pub const EMBEDDED_SCHEMAS: &[(&str, &str, &str)] = &[$((
+            $dir,
+            $name,
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/API/avro/protocol/",
+                $dir,
+                "/",
+                $name,
+                ".avsc"
+            )),
+        )),*];
+    };
+}
+
+embed_schemas![
+    ("storage", "insight.storage.TrackType"),
+    ("storage", "insight.storage.TrackInfo"),
+    ("storage", "insight.storage.Unit"),
+    ("storage", "insight.storage.UnitElementValue"),
+    ("storage", "insight.storage.UnitElementMessage"),
+    ("transport", "insight.transport.NotifyMessage"),
+    ("transport", "insight.transport.StreamTracksRequest"),
+    ("transport", "insight.transport.StreamTracksResponse"),
+    (
+        "transport",
+        "insight.transport.StreamTrackUnitElementsRequest"
+    ),
+    (
+        "transport",
+        "insight.transport.StreamTrackUnitElementsResponse"
+    ),
+    ("transport", "insight.transport.StreamTrackUnitsRequest"),
+    ("transport", "insight.transport.StreamTrackUnitsResponse"),
+    ("transport", "insight.transport.PingRequestResponse"),
+    ("transport", "insight.transport.KeepAliveMessage"),
+    ("transport", "insight.transport.MessageEnvelope"),
+    ("services/ffprobe", "insight.ffprobe.Request"),
+    ("services/ffprobe", "insight.ffprobe.Response"),
+];
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::{
+        BuilderImpl, KEEPALIVE_MESSAGE_SCHEMA, SERVICES_FFPROBE_RESPONSE_SCHEMA,
+        SERVICE_FFPROBE_SCHEMAS, STORAGE_SCHEMAS, TRACK_TYPE_SCHEMA, TRANSPORT_SCHEMAS,
+    };
+    use crate::schemas::EMBEDDED_SCHEMAS;
+
+    #[test]
+    fn test_embedded_schemas() {
+        let files = BuilderImpl::schema_files();
+        assert_eq!(files.len(), EMBEDDED_SCHEMAS.len());
+        assert!(files.contains(&(STORAGE_SCHEMAS, TRACK_TYPE_SCHEMA)));
+        assert!(files.contains(&(TRANSPORT_SCHEMAS, KEEPALIVE_MESSAGE_SCHEMA)));
+        assert!(files.contains(&(SERVICE_FFPROBE_SCHEMAS, SERVICES_FFPROBE_RESPONSE_SCHEMA)));
+
+        // Every file is listed under the full name of the schema it defines.
+        let mb = BuilderImpl::embedded().unwrap();
+        for (_, name, _) in EMBEDDED_SCHEMAS {
+            assert!(mb.directory.contains_key(*name), "{}", name);
+        }
+    }
+}
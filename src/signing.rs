@@ -0,0 +1,304 @@
+use crate::envelope::SIGNED_MAGIC;
+use crate::error::{ProtocolError, Result};
+use hmac::{Hmac, Mac};
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TAG_SIZE: usize = 32;
+
+fn check_key_id(key_id: &str) -> Result<()> {
+    if key_id.is_empty() || key_id.len() > usize::from(u8::MAX) {
+        return Err(ProtocolError::Signature(format!(
+            "Key id ({}) must be 1 to 255 bytes long",
+            key_id
+        )));
+    }
+    Ok(())
+}
+
+/// HMAC-SHA256 over the signature prefix, the key id included, and the signed message.
+fn mac(secret: &[u8], prefix: &[u8], body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(prefix);
+    mac.update(body);
+    mac
+}
+
+/// The key the outgoing messages are signed with.
+#[derive(Clone)]
+pub struct SigningKey {
+    key_id: String,
+    secret: Vec<u8>,
+}
+
+impl SigningKey {
+    /// The key id travels with every message so the receiver picks the secret from its key
+    /// ring; it must be 1 to 255 bytes long.
+    pub fn new(key_id: &str, secret: &[u8]) -> Result<Self> {
+        check_key_id(key_id)?;
+        Ok(SigningKey {
+            key_id: String::from(key_id),
+            secret: secret.to_vec(),
+        })
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// Prefixes the message with the key id and the HMAC-SHA256 tag of both.
+    pub(crate) fn sign(&self, framed: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            SIGNED_MAGIC.len() + 1 + self.key_id.len() + TAG_SIZE + framed.len(),
+        );
+        buf.extend_from_slice(&SIGNED_MAGIC);
+        buf.push(self.key_id.len() as u8);
+        buf.extend_from_slice(self.key_id.as_bytes());
+        let tag = mac(&self.secret, &buf, framed).finalize().into_bytes();
+        buf.extend_from_slice(&tag);
+        buf.extend_from_slice(framed);
+        buf
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Secrets of the trusted senders by key id. Both the old and the new key stay in the ring
+/// while the senders are rotated to the new one.
+#[derive(Clone, Default)]
+pub struct KeyRing {
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl KeyRing {
+    pub fn insert(&mut self, key_id: &str, secret: &[u8]) -> Result<()> {
+        check_key_id(key_id)?;
+        self.keys.insert(String::from(key_id), secret.to_vec());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key_id: &str) -> bool {
+        self.keys.remove(key_id).is_some()
+    }
+
+    pub fn contains(&self, key_id: &str) -> bool {
+        self.keys.contains_key(key_id)
+    }
+
+    pub fn key_ids(&self) -> Vec<&str> {
+        let mut key_ids = self.keys.keys().map(String::as_str).collect::<Vec<_>>();
+        key_ids.sort_unstable();
+        key_ids
+    }
+
+    /// Checks the signature of the message and returns the outcome with the signed message,
+    /// the message itself if it is not signed.
+    pub(crate) fn verify<'a>(&self, from: &'a [u8]) -> Result<(Signature, &'a [u8])> {
+        let signed = match Signed::split(from)? {
+            Some(signed) => signed,
+            None => return Ok((Signature::default(), from)),
+        };
+        let status = match self.keys.get(signed.key_id) {
+            None => SignatureStatus::UnknownKey,
+            Some(secret)
+                if mac(secret, signed.prefix, signed.body)
+                    .verify_slice(signed.tag)
+                    .is_ok() =>
+            {
+                SignatureStatus::Valid
+            }
+            Some(_) => SignatureStatus::Invalid,
+        };
+        Ok((
+            Signature {
+                status,
+                key_id: Some(String::from(signed.key_id)),
+            },
+            signed.body,
+        ))
+    }
+}
+
+/// A signed message split into the prefix the tag covers, the key id, the tag and the body.
+struct Signed<'a> {
+    prefix: &'a [u8],
+    key_id: &'a str,
+    tag: &'a [u8],
+    body: &'a [u8],
+}
+
+impl<'a> Signed<'a> {
+    fn split(from: &'a [u8]) -> Result<Option<Signed<'a>>> {
+        let rest = match from.strip_prefix(&SIGNED_MAGIC[..]) {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        let truncated = || ProtocolError::EnvelopeDecode(String::from("Truncated signature"));
+        let (&len, rest) = rest.split_first().ok_or_else(truncated)?;
+        let len = usize::from(len);
+        if rest.len() < len + TAG_SIZE {
+            return Err(truncated());
+        }
+        let (key_id, rest) = rest.split_at(len);
+        let (tag, body) = rest.split_at(TAG_SIZE);
+        let key_id = std::str::from_utf8(key_id).map_err(|_| {
+            ProtocolError::EnvelopeDecode(String::from("Signing key id is not a valid UTF-8"))
+        })?;
+        Ok(Some(Signed {
+            prefix: &from[..SIGNED_MAGIC.len() + 1 + len],
+            key_id,
+            tag,
+            body,
+        }))
+    }
+}
+
+/// The signed message without checking its signature, the message itself if it is not signed.
+pub(crate) fn strip_signature(from: &[u8]) -> Result<&[u8]> {
+    Ok(Signed::split(from)?.map_or(from, |signed| signed.body))
+}
+
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.key_ids()).finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub enum SignatureStatus {
+    #[default]
+    Unsigned,
+    /// Signed with a key of the ring and intact.
+    Valid,
+    /// Signed with a key of the ring, but the tag does not match the message.
+    Invalid,
+    /// Signed with a key the ring does not hold.
+    UnknownKey,
+}
+
+/// Outcome of the signature check of a received message.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "python", pyclass(get_all))]
+pub struct Signature {
+    pub status: SignatureStatus,
+    /// Key the message claims to be signed with, `None` if it is not signed.
+    pub key_id: Option<String>,
+}
+
+impl Signature {
+    pub fn is_valid(&self) -> bool {
+        self.status == SignatureStatus::Valid
+    }
+}
+
+#[cfg(feature = "python")]
+#[pymethods]
+impl Signature {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    fn __str__(&self) -> String {
+        self.__repr__()
+    }
+
+    #[classattr]
+    const __hash__: Option<Py<PyAny>> = None;
+}
+
+/// What the receiver does with the messages which are not validly signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "python", pyclass)]
+pub enum VerifyPolicy {
+    /// Decodes every message and reports the outcome of the check along with it.
+    #[default]
+    Flag,
+    /// Rejects every message but the ones validly signed with a key of the ring.
+    Require,
+}
+
+impl VerifyPolicy {
+    pub(crate) fn enforce(&self, signature: &Signature) -> Result<()> {
+        if *self == VerifyPolicy::Flag || signature.is_valid() {
+            return Ok(());
+        }
+        let key_id = signature.key_id.as_deref().unwrap_or_default();
+        Err(ProtocolError::Signature(match signature.status {
+            SignatureStatus::Unsigned => String::from("The message is not signed"),
+            SignatureStatus::UnknownKey => format!("Unknown signing key ({})", key_id),
+            _ => format!("Invalid signature with the key ({})", key_id),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ProtocolError;
+    use crate::signing::{KeyRing, Signature, SignatureStatus, SigningKey, VerifyPolicy};
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = SigningKey::new("edge-1", b"secret").unwrap();
+        let mut signed = key.sign(&[2, 4]);
+        let mut ring = KeyRing::default();
+        assert_eq!(
+            ring.verify(&signed).unwrap().0.status,
+            SignatureStatus::UnknownKey
+        );
+
+        ring.insert("edge-1", b"secret").unwrap();
+        let (signature, body) = ring.verify(&signed).unwrap();
+        assert_eq!(
+            signature,
+            Signature {
+                status: SignatureStatus::Valid,
+                key_id: Some(String::from("edge-1"))
+            }
+        );
+        assert_eq!(body, [2, 4]);
+        assert_eq!(
+            ring.verify(&[2, 4]).unwrap(),
+            (Signature::default(), &[2, 4][..])
+        );
+
+        let last = signed.len() - 1;
+        signed[last] ^= 0x01;
+        assert_eq!(
+            ring.verify(&signed).unwrap().0.status,
+            SignatureStatus::Invalid
+        );
+        assert!(matches!(
+            ring.verify(&signed[..10]),
+            Err(ProtocolError::EnvelopeDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_policy() {
+        let unsigned = Signature::default();
+        let valid = Signature {
+            status: SignatureStatus::Valid,
+            key_id: Some(String::from("edge-1")),
+        };
+        assert_eq!(VerifyPolicy::Flag.enforce(&unsigned), Ok(()));
+        assert_eq!(VerifyPolicy::Require.enforce(&valid), Ok(()));
+        assert!(matches!(
+            VerifyPolicy::Require.enforce(&unsigned),
+            Err(ProtocolError::Signature(_))
+        ));
+        assert!(SigningKey::new("", b"secret").is_err());
+        assert!(KeyRing::default().insert(&"k".repeat(256), b"").is_err());
+    }
+}
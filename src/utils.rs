@@ -1,16 +1,18 @@
+use crate::error::{ProtocolError, Result};
 use avro_rs::types::Value;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn load_file(prefix: &Path, schema_name: &str) -> String {
+pub fn load_file(prefix: &Path, schema_name: &str) -> Result<String> {
     let path = prefix.join(schema_name);
-    fs::read_to_string(&path).unwrap_or_else(|e| {
-        panic!(
+    fs::read_to_string(&path).map_err(|e| {
+        ProtocolError::SchemaLoad(format!(
             "File {} cannot be loaded. Error is {:?}",
-            &path.to_str().unwrap(),
+            path.display(),
             e
-        )
+        ))
     })
 }
 
@@ -29,13 +31,54 @@ pub fn value_to_string(v: &Value) -> Option<String> {
     }
 }
 
-pub fn fill_byte_array(buf: &mut [u8], from: &Vec<u8>) {
+pub fn value_to_string_map(
+    schema: &str,
+    field: &str,
+    map: &HashMap<String, Value>,
+) -> Result<HashMap<String, String>> {
+    map.iter()
+        .map(|(k, v)| {
+            value_to_string(v)
+                .map(|v| (k.clone(), v))
+                .ok_or_else(|| ProtocolError::FieldMismatch {
+                    schema: String::from(schema),
+                    field: String::from(field),
+                })
+        })
+        .collect()
+}
+
+pub fn fill_byte_array(buf: &mut [u8], from: &[u8]) {
     let len = std::cmp::min(buf.len(), from.len());
-    buf[..len].clone_from_slice(from.as_slice());
+    buf[..len].clone_from_slice(&from[..len]);
+}
+
+/// Copies `from` into a zero-padded array, failing if it does not fit.
+pub fn to_byte_array<const N: usize>(field: &str, from: &[u8]) -> Result<[u8; N]> {
+    if from.len() > N {
+        return Err(ProtocolError::ValueOutOfRange {
+            field: String::from(field),
+            value: format!("{} bytes", from.len()),
+        });
+    }
+    let mut buf = [0; N];
+    fill_byte_array(&mut buf, from);
+    Ok(buf)
+}
+
+pub fn checked_cast<F, T>(field: &str, value: F) -> Result<T>
+where
+    F: Copy + Display,
+    T: TryFrom<F>,
+{
+    T::try_from(value).map_err(|_| ProtocolError::ValueOutOfRange {
+        field: String::from(field),
+        value: value.to_string(),
+    })
 }
 
 pub fn get_avro_path() -> String {
     let mut base_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     base_dir.push("API/avro/protocol");
-    return String::from(base_dir.to_str().unwrap());
+    String::from(base_dir.to_str().unwrap())
 }